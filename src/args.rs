@@ -0,0 +1,210 @@
+use std::path::Path;
+
+pub const USAGE: &str = "\
+Usage: quik [OPTIONS] [+LINE] [FILE[:LINE[:COL]]]...
+
+Options:
+  -R, --readonly  Open the files without allowing changes
+  -h, --help      Print this help
+  -V, --version   Print the version
+
++LINE opens the following file at the given line.";
+
+// A file to open, with the position to put the caret at. Lines and columns count from 1.
+pub struct FileArg {
+    pub name: String,
+    pub line: Option<usize>,
+    pub col: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct Args {
+    pub files: Vec<FileArg>,
+    pub readonly: bool,
+}
+
+pub enum Action {
+    Edit(Args),
+    Help,
+    Version,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Action, String> {
+        let mut parsed = Self::default();
+        let mut line = None; // given with +LINE, for the next file
+        let mut only_files = false; // after "--", everything is a file name
+        for arg in args {
+            if only_files || !arg.starts_with(['-', '+']) || arg == "-" {
+                parsed.files.push(FileArg::parse(arg, line.take()));
+                continue;
+            }
+            match arg.as_str() {
+                "--" => only_files = true,
+                "-R" | "--readonly" => parsed.readonly = true,
+                "-h" | "--help" => return Ok(Action::Help),
+                "-V" | "--version" => return Ok(Action::Version),
+                _ => {
+                    let Some(number) = arg.strip_prefix('+') else {
+                        return Err(format!("unknown option '{arg}'"));
+                    };
+                    let number = number
+                        .parse()
+                        .map_err(|_| format!("invalid line number '{arg}'"))?;
+                    line = Some(number);
+                }
+            }
+        }
+        if let Some(line) = line {
+            return Err(format!("missing file name after '+{line}'"));
+        }
+        Ok(Action::Edit(parsed))
+    }
+}
+
+impl FileArg {
+    // Splits a position in the form of `name:line:col` or `name:line` off the file name, as printed by compilers.
+    // Files which exist under the full name are taken literally.
+    fn parse(arg: String, line: Option<usize>) -> Self {
+        let literal = |name: String| Self {
+            name,
+            line,
+            col: None,
+        };
+        if Path::new(&arg).exists() {
+            return literal(arg);
+        }
+        let trimmed = arg.strip_suffix(':').unwrap_or(&arg);
+        let (name, file_line, col) = match split_number_suffix(trimmed) {
+            // The name itself may end in a number, like `notes:12` in `notes:12:3`
+            Some((rest, last)) if Path::new(rest).exists() => (rest, last, None),
+            Some((rest, last)) => match split_number_suffix(rest) {
+                Some((name, file_line)) => (name, file_line, Some(last)),
+                None => (rest, last, None),
+            },
+            None => return literal(arg),
+        };
+        if name.is_empty() {
+            return literal(arg);
+        }
+        Self {
+            name: name.to_string(),
+            line: Some(file_line),
+            col,
+        }
+    }
+}
+
+// Splits a trailing `:number` off the text.
+fn split_number_suffix(text: &str) -> Option<(&str, usize)> {
+    text.rsplit_once(':')
+        .and_then(|(rest, number)| number.parse().ok().map(|number| (rest, number)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        match Args::parse(args.iter().map(ToString::to_string))? {
+            Action::Edit(args) => Ok(args),
+            Action::Help => Err(String::from("help")),
+            Action::Version => Err(String::from("version")),
+        }
+    }
+
+    fn files(args: &[&str]) -> Vec<(String, Option<usize>, Option<usize>)> {
+        parse(args)
+            .unwrap()
+            .files
+            .into_iter()
+            .map(|file| (file.name, file.line, file.col))
+            .collect()
+    }
+
+    fn file(
+        name: &str,
+        line: Option<usize>,
+        col: Option<usize>,
+    ) -> (String, Option<usize>, Option<usize>) {
+        (name.to_string(), line, col)
+    }
+
+    #[test]
+    fn positions_after_file_names() {
+        assert_eq!(
+            files(&["src/main.rs:12:3"]),
+            [file("src/main.rs", Some(12), Some(3))]
+        );
+        assert_eq!(
+            files(&["src/main.rs:12"]),
+            [file("src/main.rs", Some(12), None)]
+        );
+        assert_eq!(
+            files(&["src/main.rs:12:"]),
+            [file("src/main.rs", Some(12), None)]
+        );
+        assert_eq!(
+            files(&["src/main.rs:x"]),
+            [file("src/main.rs:x", None, None)]
+        );
+        assert_eq!(files(&[":12"]), [file(":12", None, None)]);
+    }
+
+    #[test]
+    fn line_given_before_file_name() {
+        assert_eq!(
+            files(&["+42", "a.rs", "b.rs"]),
+            [file("a.rs", Some(42), None), file("b.rs", None, None)]
+        );
+        assert_eq!(
+            parse(&["a.rs", "+42"]).err().unwrap(),
+            "missing file name after '+42'"
+        );
+        assert_eq!(
+            parse(&["+x", "a.rs"]).err().unwrap(),
+            "invalid line number '+x'"
+        );
+    }
+
+    #[test]
+    fn options() {
+        let args = parse(&["-R", "a.rs"]).unwrap();
+        assert!(args.readonly);
+        assert!(parse(&["--readonly"]).unwrap().readonly);
+        assert!(!parse(&["a.rs"]).unwrap().readonly);
+        assert_eq!(parse(&["a.rs", "--help"]).err().unwrap(), "help");
+        assert_eq!(parse(&["-V"]).err().unwrap(), "version");
+        assert_eq!(
+            parse(&["--frobnicate"]).err().unwrap(),
+            "unknown option '--frobnicate'"
+        );
+    }
+
+    #[test]
+    fn everything_after_double_dash_is_a_file() {
+        assert_eq!(
+            files(&["--", "-R", "+3", "--", "a.rs:2"]),
+            [
+                file("-R", None, None),
+                file("+3", None, None),
+                file("--", None, None),
+                file("a.rs", Some(2), None)
+            ]
+        );
+        assert!(!parse(&["--", "-R"]).unwrap().readonly);
+    }
+
+    #[test]
+    fn existing_file_with_colon_is_taken_literally() {
+        let dir = env::temp_dir().join(format!("quik-args-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes:12");
+        fs::write(&path, "").unwrap();
+        let name = path.display().to_string();
+        assert_eq!(files(&[&name]), [file(&name, None, None)]);
+        assert_eq!(files(&[&format!("{name}:3")]), [file(&name, Some(3), None)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
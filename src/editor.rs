@@ -1,37 +1,61 @@
 use crossterm::event::{read, Event, KeyEvent, KeyEventKind};
 use std::{
-    env,
+    cell::RefCell,
+    cmp::min,
     io::Error,
+    mem,
     panic::{set_hook, take_hook},
+    rc::Rc,
 };
+mod annotatedstring;
+mod annotation;
+mod clipboard;
 mod command;
 mod commandbar;
+mod config;
 mod documentstatus;
+mod filetype;
+mod layout;
 mod line;
 mod messagebar;
 mod position;
+mod prompthistory;
 mod size;
 mod statusbar;
 mod terminal;
 mod uicomponent;
 mod view;
 
+use annotatedstring::AnnotatedString;
+use annotation::{Annotation, AnnotationType};
+use clipboard::{Clipboard, ClipboardContent};
 use commandbar::CommandBar;
+use config::Config;
 use documentstatus::DocumentStatus;
+use filetype::{FileType, FileTypeSettings};
+use layout::{Layout, SplitDirection};
 use line::Line;
 use messagebar::MessageBar;
 use position::{Col, Position, Row};
+use prompthistory::PromptHistory;
 use size::Size;
 use statusbar::StatusBar;
 use terminal::Terminal;
 use uicomponent::UIComponent;
-use view::View;
+use view::{Buffer, GoToTarget, SearchOptions, SharedBuffer, View};
+
+use crate::args::Args;
 
 use self::command::{
-    Command::{self, Edit, Move, System},
-    Edit::InsertNewline,
-    Move::{Down, Left, Right, Up},
-    System::{Dismiss, Quit, Resize, Save, Search},
+    Command::{self, Edit, Move, Select, System},
+    Edit::{Insert, InsertNewline},
+    Move::{Down, Up},
+    System::{
+        CloseBuffer, ClosePane, Copy, Cut, Dismiss, FocusNextPane, FocusPreviousPane, GoTo,
+        ListBuffers, NextBuffer, NextHistoryEntry, Paste, PreviousBuffer, PreviousHistoryEntry,
+        Quit, Replace, Resize, Save, Search, SplitHorizontal, SplitVertical, ToggleCaseSensitivity,
+        ToggleLineNumbers, ToggleRegex, ToggleSoftWrap, ToggleWholeWord,
+    },
 };
 pub const NAME: &str = env!("CARGO_PKG_NAME");
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -42,6 +66,10 @@ const QUIT_TIMES: u8 = 3;
 enum PromptType {
     Search,
     Save,
+    Replace,
+    ReplaceWith,
+    ConfirmReplace,
+    GoTo,
     #[default]
     None,
 }
@@ -50,24 +78,42 @@ impl PromptType {
     fn is_none(&self) -> bool {
         *self == Self::None
     }
+    // The name under which the values entered into this prompt are kept in the history
+    fn history_kind(&self) -> Option<&'static str> {
+        match self {
+            Self::Search => Some("search"),
+            Self::Save => Some("save"),
+            Self::Replace => Some("replace"),
+            Self::ReplaceWith => Some("replace_with"),
+            Self::GoTo => Some("go_to"),
+            Self::ConfirmReplace | Self::None => None,
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct Editor {
     should_quit: bool,
-    view: View,
+    buffers: Vec<SharedBuffer>, // all open buffers, each shown in any number of panes
+    layout: Layout,
     status_bar: StatusBar,
     message_bar: MessageBar,
     command_bar: CommandBar,
+    clipboard: Clipboard,
+    config: Config,
+    search_options: SearchOptions, // kept for all searches until changed in the search prompt
+    replace_query: String, // what to replace, while the replacement is being asked for
+    prompt_history: PromptHistory,
     prompt_type: PromptType,
     terminal_size: Size,
     title: String,
     quit_times: u8,
+    close_requested: bool,
 }
 
 impl Editor {
 
-    pub fn new() -> Result<Self, Error> {
+    pub fn new(args: Args) -> Result<Self, Error> {
         let current_hook = take_hook();
         set_hook(Box::new(move |panic_info| {
             let _ = Terminal::terminate();
@@ -76,22 +122,64 @@ impl Editor {
         Terminal::initialize()?;
 
         let mut editor = Self::default();
-        let size = Terminal::size().unwrap_or_default();
-        editor.handle_resize_command(size);
+        editor.clipboard = Clipboard::from_env();
         editor.update_message("HELP: Ctrl-F = find | Ctrl-S = save | Ctrl-Q = quit");
 
-        let args: Vec<String> = env::args().collect();
-        if let Some(file_name) = args.get(1) {
-            debug_assert!(!file_name.is_empty());
-            if editor.view.load(file_name).is_err() {
-                editor.update_message(&format!("ERR: Could not open file: {file_name}"));
+        match Config::load() {
+            Ok(config) => editor.config = config,
+            Err(err) => editor.update_message(&format!("ERR: Could not read config: {err}")),
+        }
+        match PromptHistory::load() {
+            Ok(history) => editor.prompt_history = history,
+            Err(err) => editor.update_message(&format!("ERR: Could not read history: {err}")),
+        }
+
+        let mut positions = Vec::new();
+        for file in args.files {
+            debug_assert!(!file.name.is_empty());
+            if let Ok(mut buffer) = Buffer::load(&file.name, &editor.config) {
+                buffer.readonly = args.readonly;
+                let buffer = Rc::new(RefCell::new(buffer));
+                if let Some(line) = file.line {
+                    positions.push((Rc::clone(&buffer), GoToTarget::Line { line, col: file.col }));
+                }
+                editor.buffers.push(buffer);
+            } else {
+                editor.update_message(&format!("ERR: Could not open file: {}", file.name));
             }
         }
+        if editor.buffers.is_empty() {
+            editor.buffers.push(SharedBuffer::default());
+        }
+        if let Some(buffer) = editor.buffers.first() {
+            editor.layout = Layout::new(View::new(Rc::clone(buffer)));
+        }
+        let size = Terminal::size().unwrap_or_default();
+        editor.handle_resize_command(size);
+        editor.apply_positions(positions);
         editor.refresh_status();
         Ok(editor)
     }
 
 
+    // Moves the caret of each buffer to the position requested on the command line.
+    // Positions are applied by showing the buffer, so they can be centred, and remembered for when it's shown again.
+    fn apply_positions(&mut self, positions: Vec<(SharedBuffer, GoToTarget)>) {
+        if positions.is_empty() {
+            return;
+        }
+        for (buffer, target) in positions {
+            self.view_mut().set_buffer(buffer);
+            if let Err(err) = self.view_mut().go_to(target) {
+                self.update_message(&format!("ERR: {err}"));
+            }
+        }
+        if let Some(buffer) = self.buffers.first() {
+            let buffer = Rc::clone(buffer);
+            self.view_mut().set_buffer(buffer);
+        }
+    }
+
     pub fn run(&mut self) {
         loop {
             self.refresh_screen();
@@ -115,23 +203,39 @@ impl Editor {
         }
     }
 
+    // clippy::expect_used: The layout always contains at least one pane, and one of them is focused.
+    #[allow(clippy::expect_used)]
+    fn view(&self) -> &View {
+        self.layout.focused_view().expect("No focused view")
+    }
+    #[allow(clippy::expect_used)]
+    fn view_mut(&mut self) -> &mut View {
+        self.layout.focused_view_mut().expect("No focused view")
+    }
+
     fn refresh_screen(&mut self) {
         if self.terminal_size.height == 0 || self.terminal_size.width == 0 {
             return;
         }
         let bottom_bar_row = self.terminal_size.height.saturating_sub(1);
         let _ = Terminal::hide_caret();
+        let bottom_bar_origin = Position {
+            row: bottom_bar_row,
+            col: 0,
+        };
         if self.in_prompt() {
-            self.command_bar.render(bottom_bar_row);
+            self.command_bar.render(bottom_bar_origin);
         } else {
-            self.message_bar.render(bottom_bar_row);
+            self.message_bar.render(bottom_bar_origin);
         }
         if self.terminal_size.height > 1 {
-            self.status_bar
-                .render(self.terminal_size.height.saturating_sub(2));
+            self.status_bar.render(Position {
+                row: self.terminal_size.height.saturating_sub(2),
+                col: 0,
+            });
         }
         if self.terminal_size.height > 2 {
-            self.view.render(0);
+            self.layout.render(Position::default());
         }
         let new_caret_pos = if self.in_prompt() {
             Position {
@@ -139,7 +243,7 @@ impl Editor {
                 col: self.command_bar.caret_position_col(),
             }
         } else {
-            self.view.caret_position()
+            self.layout.caret_position()
         };
         debug_assert!(new_caret_pos.col <= self.terminal_size.width);
         debug_assert!(new_caret_pos.row <= self.terminal_size.height);
@@ -150,7 +254,7 @@ impl Editor {
     }
 
     fn refresh_status(&mut self) {
-        let status = self.view.get_status();
+        let status = self.view().get_status();
         let title = format!("{} - {NAME}", status.file_name);
         self.status_bar.update_status(status);
         if title != self.title && matches!(Terminal::set_title(&title), Ok(())) {
@@ -180,6 +284,11 @@ impl Editor {
         match self.prompt_type {
             PromptType::Search => self.process_command_during_search(command),
             PromptType::Save => self.process_command_during_save(command),
+            PromptType::Replace | PromptType::ReplaceWith => {
+                self.process_command_during_replace(command);
+            }
+            PromptType::ConfirmReplace => self.process_command_during_replace_confirmation(command),
+            PromptType::GoTo => self.process_command_during_go_to(command),
             PromptType::None => self.process_command_no_prompt(command),
         }
     }
@@ -190,20 +299,87 @@ impl Editor {
             return;
         }
         self.reset_quit_times(); // Reset quit times for all other commands
+        if matches!(command, System(CloseBuffer)) {
+            self.handle_close_buffer_command();
+            return;
+        }
+        self.reset_close_requested();
+        if self.view().is_readonly() && matches!(command, Edit(_) | System(Cut | Paste | Replace)) {
+            self.update_message("Cannot change a read-only buffer.");
+            return;
+        }
+        if self.view().is_readonly() && matches!(command, System(Save)) {
+            self.update_message("Cannot save a read-only buffer.");
+            return;
+        }
 
         match command {
-            System(Quit | Resize(_) | Dismiss) => {} // Quit and Resize already handled above, others not applicable
+            System(
+                Quit | Resize(_) | Dismiss | CloseBuffer | ToggleRegex | ToggleCaseSensitivity
+                | ToggleWholeWord | PreviousHistoryEntry | NextHistoryEntry,
+            ) => {} // Quit, Resize and CloseBuffer already handled above, others not applicable
             System(Search) => self.set_prompt(PromptType::Search),
+            System(Replace) => self.set_prompt(PromptType::Replace),
+            System(GoTo) => self.set_prompt(PromptType::GoTo),
             System(Save) => self.handle_save_command(),
-            Edit(edit_command) => self.view.handle_edit_command(edit_command),
-            Move(move_command) => self.view.handle_move_command(move_command),
+            System(NextBuffer) => self.switch_buffer(self.next_buffer_idx()),
+            System(PreviousBuffer) => self.switch_buffer(self.previous_buffer_idx()),
+            System(ListBuffers) => self.handle_list_buffers_command(),
+            System(SplitHorizontal) => self.handle_split_command(SplitDirection::Horizontal),
+            System(SplitVertical) => self.handle_split_command(SplitDirection::Vertical),
+            System(ClosePane) => {
+                if !self.layout.close_focused() {
+                    self.update_message("Cannot close the last pane.");
+                }
+            }
+            System(FocusNextPane) => self.layout.focus_next(),
+            System(FocusPreviousPane) => self.layout.focus_previous(),
+            System(ToggleLineNumbers) => {
+                let line_numbers = self.view_mut().cycle_line_numbers();
+                self.update_message(&format!("Line numbers: {line_numbers}"));
+            }
+            System(ToggleSoftWrap) => {
+                let state = if self.view_mut().toggle_soft_wrap() { "on" } else { "off" };
+                self.update_message(&format!("Soft wrap: {state}"));
+            }
+            System(Cut) => {
+                if let Some(content) = self.view_mut().cut() {
+                    self.set_clipboard(content);
+                }
+            }
+            System(Copy) => {
+                if let Some(content) = self.view_mut().copy() {
+                    self.set_clipboard(content);
+                }
+            }
+            System(Paste) => match self.clipboard.get() {
+                Ok(Some(content)) => self.view_mut().paste(&content),
+                Ok(None) => {}
+                Err(err) => self.update_message(&format!("ERR: Could not paste: {err}")),
+            },
+            Edit(edit_command) => self.view_mut().handle_edit_command(edit_command),
+            Move(move_command) => self.view_mut().handle_move_command(move_command),
+            Select(move_command) => self.view_mut().handle_select_command(move_command),
         }
     }
 
 
+    fn paste_into_prompt(&mut self) {
+        match self.clipboard.get() {
+            Ok(Some(content)) => self.command_bar.insert_str(&content.text),
+            Ok(None) => {}
+            Err(err) => self.update_message(&format!("ERR: Could not paste: {err}")),
+        }
+    }
+    fn set_clipboard(&mut self, content: ClipboardContent) {
+        if let Err(err) = self.clipboard.set(content) {
+            self.update_message(&format!("ERR: Could not copy to system clipboard: {err}"));
+        }
+    }
+
     fn handle_resize_command(&mut self, size: Size) {
         self.terminal_size = size;
-        self.view.resize(Size {
+        self.layout.resize(Size {
             height: size.height.saturating_sub(2),
             width: size.width,
         });
@@ -218,11 +394,21 @@ impl Editor {
 
     #[allow(clippy::arithmetic_side_effects)]
     fn handle_quit_command(&mut self) {
-        if !self.view.get_status().is_modified || self.quit_times + 1 == QUIT_TIMES {
+        let modified_count = self
+            .buffers
+            .iter()
+            .filter(|buffer| buffer.borrow().dirty)
+            .count();
+        if modified_count == 0 || self.quit_times + 1 == QUIT_TIMES {
             self.should_quit = true;
-        } else if self.view.get_status().is_modified {
+        } else {
+            let files = if modified_count == 1 {
+                String::from("File has")
+            } else {
+                format!("{modified_count} files have")
+            };
             self.update_message(&format!(
-                "WARNING! File has unsaved changes. Press Ctrl-Q {} more times to quit.",
+                "WARNING! {files} unsaved changes. Press Ctrl-Q {} more times to quit.",
                 QUIT_TIMES - self.quit_times - 1
             ));
 
@@ -236,8 +422,89 @@ impl Editor {
         }
     }
 
+    // The index of the buffer shown in the focused pane
+    fn current_buffer_idx(&self) -> usize {
+        let current = self.view().buffer();
+        self.buffers
+            .iter()
+            .position(|buffer| Rc::ptr_eq(buffer, current))
+            .unwrap_or(0)
+    }
+    fn next_buffer_idx(&self) -> usize {
+        let next = self.current_buffer_idx().saturating_add(1);
+        if next < self.buffers.len() {
+            next
+        } else {
+            0
+        }
+    }
+    fn previous_buffer_idx(&self) -> usize {
+        self.current_buffer_idx()
+            .checked_sub(1)
+            .unwrap_or_else(|| self.buffers.len().saturating_sub(1))
+    }
+    fn switch_buffer(&mut self, buffer_idx: usize) {
+        if let Some(buffer) = self.buffers.get(buffer_idx).map(Rc::clone) {
+            self.view_mut().set_buffer(buffer);
+        }
+    }
+    // Closes the current buffer, asking for confirmation once if it has unsaved changes.
+    // All panes showing it switch to the next buffer. Closing the last buffer leaves an empty one behind.
+    fn handle_close_buffer_command(&mut self) {
+        if self.view().get_status().is_modified && !self.close_requested {
+            self.close_requested = true;
+            self.update_message(
+                "WARNING! File has unsaved changes. Press Ctrl-W again to close it anyway.",
+            );
+            return;
+        }
+        self.reset_close_requested();
+        let buffer_idx = self.current_buffer_idx();
+        let closed = self.buffers.remove(buffer_idx);
+        if self.buffers.is_empty() {
+            self.buffers.push(SharedBuffer::default());
+        }
+        let replacement = self
+            .buffers
+            .get(min(buffer_idx, self.buffers.len().saturating_sub(1)))
+            .map(Rc::clone)
+            .unwrap_or_default();
+        for pane in self.layout.panes_mut() {
+            if Rc::ptr_eq(pane.view.buffer(), &closed) {
+                pane.view.set_buffer(Rc::clone(&replacement));
+            }
+        }
+    }
+    fn handle_split_command(&mut self, direction: SplitDirection) {
+        if !self.layout.split(direction) {
+            self.update_message("Not enough room to split the pane.");
+        }
+    }
+    fn reset_close_requested(&mut self) {
+        if self.close_requested {
+            self.close_requested = false;
+            self.update_message("");
+        }
+    }
+    fn handle_list_buffers_command(&mut self) {
+        let current_idx = self.current_buffer_idx();
+        let list = self
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(idx, buffer)| {
+                let buffer = buffer.borrow();
+                let marker = if idx == current_idx { ">" } else { "" };
+                let modified = if buffer.dirty { "+" } else { "" };
+                format!("{marker}{}:{}{modified}", idx.saturating_add(1), buffer.file_info)
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        self.update_message(&format!("Buffers: {list}"));
+    }
+
     fn handle_save_command(&mut self) {
-        if self.view.is_file_loaded() {
+        if self.view().is_file_loaded() {
             self.save(None);
         } else {
             self.set_prompt(PromptType::Save);
@@ -245,7 +512,21 @@ impl Editor {
     }
     fn process_command_during_save(&mut self, command: Command) {
         match command {
-            System(Quit | Resize(_) | Search | Save) | Move(_) => {} // Not applicable during save, Resize already handled at this stage
+            System(PreviousHistoryEntry) | Move(Up) => {
+                self.command_bar.previous_history_entry();
+            }
+            System(NextHistoryEntry) | Move(Down) => {
+                self.command_bar.next_history_entry();
+            }
+            System(
+                Quit | Resize(_) | Search | Save | Cut | Copy | NextBuffer | PreviousBuffer
+                | ListBuffers | CloseBuffer | SplitHorizontal | SplitVertical | ClosePane
+                | FocusNextPane | FocusPreviousPane | ToggleLineNumbers | ToggleSoftWrap
+                | ToggleRegex | ToggleCaseSensitivity | ToggleWholeWord | Replace | GoTo,
+            )
+            | Select(_) => {} // Not applicable during save, Resize already handled at this stage
+            System(Paste) => self.paste_into_prompt(),
+            Move(move_command) => self.command_bar.handle_move_command(move_command),
             System(Dismiss) => {
                 self.set_prompt(PromptType::None);
                 self.update_message("Save aborted.");
@@ -253,6 +534,7 @@ impl Editor {
             Edit(InsertNewline) => {
                 let file_name = self.command_bar.value();
                 self.save(Some(&file_name));
+                self.add_to_prompt_history(&file_name);
                 self.set_prompt(PromptType::None);
             }
             Edit(edit_command) => self.command_bar.handle_edit_command(edit_command),
@@ -260,9 +542,12 @@ impl Editor {
     }
     fn save(&mut self, file_name: Option<&str>) {
         let result = if let Some(name) = file_name {
-            self.view.save_as(name)
+            let config = &self.config;
+            self.layout
+                .focused_view_mut()
+                .map_or(Ok(()), |view| view.save_as(name, config))
         } else {
-            self.view.save()
+            self.view_mut().save()
         };
         if result.is_ok() {
             self.update_message("File saved successfully.");
@@ -275,22 +560,241 @@ impl Editor {
         match command {
             System(Dismiss) => {
                 self.set_prompt(PromptType::None);
-                self.view.dismiss_search();
+                self.view_mut().dismiss_search();
             }
             Edit(InsertNewline) => {
+                let query = self.command_bar.value();
+                self.add_to_prompt_history(&query);
+                if let Err(err) = self.search_options.compile(&query) {
+                    self.report_invalid_pattern(&err);
+                }
                 self.set_prompt(PromptType::None);
-                self.view.exit_search();
+                self.view_mut().exit_search();
             }
             Edit(edit_command) => {
                 self.command_bar.handle_edit_command(edit_command);
-                let query = self.command_bar.value();
-                self.view.search(&query);
+                self.search();
             }
-            Move(Right | Down) => self.view.search_next(),
-            Move(Up | Left) => self.view.search_prev(),
-            System(Quit | Resize(_) | Search | Save) | Move(_) => {} // Not applicable during save, Resize already handled at this stage
+            System(option @ (ToggleRegex | ToggleCaseSensitivity | ToggleWholeWord)) => {
+                self.toggle_search_option(option);
+                self.search();
+            }
+            System(PreviousHistoryEntry) => {
+                if self.command_bar.previous_history_entry() {
+                    self.search();
+                }
+            }
+            System(NextHistoryEntry) => {
+                if self.command_bar.next_history_entry() {
+                    self.search();
+                }
+            }
+            System(Paste) => {
+                self.paste_into_prompt();
+                self.search();
+            }
+            Move(Down) => {
+                self.view_mut().search_next();
+                self.update_search_prompt();
+            }
+            Move(Up) => {
+                self.view_mut().search_prev();
+                self.update_search_prompt();
+            }
+            Move(move_command) => self.command_bar.handle_move_command(move_command),
+            System(
+                Quit | Resize(_) | Search | Save | Cut | Copy | NextBuffer | PreviousBuffer
+                | ListBuffers | CloseBuffer | SplitHorizontal | SplitVertical | ClosePane
+                | FocusNextPane | FocusPreviousPane | ToggleLineNumbers | ToggleSoftWrap | Replace
+                | GoTo,
+            )
+            | Select(_) => {} // Not applicable during search, Resize already handled at this stage
         }
     }
+    // Searches for the value of the command bar. The prompt shows whether the pattern is invalid.
+    fn search(&mut self) {
+        let query = self.command_bar.value();
+        let options = self.search_options;
+        self.view_mut().search(&query, options);
+        self.update_search_prompt();
+    }
+    // Shows the search options, whether the pattern is invalid and the match count in the prompt.
+    fn update_search_prompt(&mut self) {
+        let query = self.command_bar.value();
+        let is_invalid = !query.is_empty() && self.search_options.compile(&query).is_err();
+        let count = self
+            .view_mut()
+            .match_count()
+            .map(|count| format!("{count}, "))
+            .unwrap_or_default();
+        self.command_bar.set_prompt(&format!(
+            "Search{} ({count}Esc to cancel, Up/Down to navigate): ",
+            self.search_flags(is_invalid)
+        ));
+    }
+    fn report_invalid_pattern(&mut self, err: &regex::Error) {
+        // Regex errors span several lines, pointing at the problem in the pattern. The last one describes it.
+        let description = err.to_string();
+        let reason = description
+            .lines()
+            .last()
+            .unwrap_or_default()
+            .trim_start_matches("error: ")
+            .to_string();
+        self.update_message(&format!("ERR: Invalid pattern: {reason}"));
+    }
+    fn process_command_during_replace(&mut self, command: Command) {
+        match command {
+            System(Dismiss) => {
+                self.set_prompt(PromptType::None);
+                self.update_message("Replace aborted.");
+            }
+            System(option @ (ToggleRegex | ToggleCaseSensitivity | ToggleWholeWord))
+                if self.prompt_type == PromptType::Replace =>
+            {
+                self.toggle_search_option(option);
+                self.command_bar.set_prompt(&self.replace_prompt());
+            }
+            System(PreviousHistoryEntry) | Move(Up) => {
+                self.command_bar.previous_history_entry();
+            }
+            System(NextHistoryEntry) | Move(Down) => {
+                self.command_bar.next_history_entry();
+            }
+            Edit(InsertNewline) if self.prompt_type == PromptType::Replace => {
+                self.replace_query = self.command_bar.value();
+                self.add_to_prompt_history(&self.replace_query.clone());
+                self.set_prompt(PromptType::ReplaceWith);
+            }
+            Edit(InsertNewline) => {
+                self.add_to_prompt_history(&self.command_bar.value());
+                self.start_replace();
+            }
+            Edit(edit_command) => self.command_bar.handle_edit_command(edit_command),
+            System(Paste) => self.paste_into_prompt(),
+            Move(move_command) => self.command_bar.handle_move_command(move_command),
+            System(
+                Quit | Resize(_) | Search | Save | Cut | Copy | NextBuffer | PreviousBuffer
+                | ListBuffers | CloseBuffer | SplitHorizontal | SplitVertical | ClosePane
+                | FocusNextPane | FocusPreviousPane | ToggleLineNumbers | ToggleSoftWrap
+                | ToggleRegex | ToggleCaseSensitivity | ToggleWholeWord | Replace | GoTo,
+            )
+            | Select(_) => {} // Not applicable during replace, Resize already handled at this stage
+        }
+    }
+    fn process_command_during_go_to(&mut self, command: Command) {
+        match command {
+            System(Dismiss) => self.set_prompt(PromptType::None),
+            System(PreviousHistoryEntry) | Move(Up) => {
+                self.command_bar.previous_history_entry();
+            }
+            System(NextHistoryEntry) | Move(Down) => {
+                self.command_bar.next_history_entry();
+            }
+            Edit(InsertNewline) => {
+                let input = self.command_bar.value();
+                self.add_to_prompt_history(&input);
+                self.set_prompt(PromptType::None);
+                let result = GoToTarget::try_from(input.as_str())
+                    .and_then(|target| self.view_mut().go_to(target));
+                if let Err(err) = result {
+                    self.update_message(&format!("ERR: {err}"));
+                }
+            }
+            Edit(edit_command) => self.command_bar.handle_edit_command(edit_command),
+            System(Paste) => self.paste_into_prompt(),
+            Move(move_command) => self.command_bar.handle_move_command(move_command),
+            System(
+                Quit | Resize(_) | Search | Save | Cut | Copy | NextBuffer | PreviousBuffer
+                | ListBuffers | CloseBuffer | SplitHorizontal | SplitVertical | ClosePane
+                | FocusNextPane | FocusPreviousPane | ToggleLineNumbers | ToggleSoftWrap
+                | ToggleRegex | ToggleCaseSensitivity | ToggleWholeWord | Replace | GoTo,
+            )
+            | Select(_) => {} // Not applicable during go to, Resize already handled at this stage
+        }
+    }
+    fn start_replace(&mut self) {
+        let replacement = self.command_bar.value();
+        let query = mem::take(&mut self.replace_query);
+        if query.is_empty() {
+            self.set_prompt(PromptType::None);
+            self.update_message("Replace aborted.");
+            return;
+        }
+        let options = self.search_options;
+        match self.view_mut().start_replace(&query, &replacement, options) {
+            Ok(()) if self.view().has_replacement() => self.set_prompt(PromptType::ConfirmReplace),
+            Ok(()) => self.finish_replace(),
+            Err(err) => {
+                self.set_prompt(PromptType::None);
+                self.report_invalid_pattern(&err);
+            }
+        }
+    }
+    fn process_command_during_replace_confirmation(&mut self, command: Command) {
+        let should_finish = match command {
+            Edit(Insert('y')) => {
+                self.view_mut().replace_current();
+                false
+            }
+            Edit(Insert('n')) => {
+                self.view_mut().skip_current();
+                false
+            }
+            Edit(Insert('a')) => {
+                self.view_mut().replace_all();
+                false
+            }
+            Edit(Insert('q')) | System(Dismiss) => true,
+            System(
+                Quit | Resize(_) | Search | Save | Cut | Copy | Paste | NextBuffer | PreviousBuffer
+                | ListBuffers | CloseBuffer | SplitHorizontal | SplitVertical | ClosePane
+                | FocusNextPane | FocusPreviousPane | ToggleLineNumbers | ToggleSoftWrap
+                | ToggleRegex | ToggleCaseSensitivity | ToggleWholeWord | Replace
+                | PreviousHistoryEntry | NextHistoryEntry | GoTo,
+            )
+            | Edit(_)
+            | Move(_)
+            | Select(_) => false, // Not applicable while confirming, Resize already handled at this stage
+        };
+        if should_finish || !self.view().has_replacement() {
+            self.finish_replace();
+        }
+    }
+    fn finish_replace(&mut self) {
+        let count = self.view_mut().finish_replace();
+        self.set_prompt(PromptType::None);
+        let occurrences = if count == 1 {
+            "occurrence"
+        } else {
+            "occurrences"
+        };
+        self.update_message(&format!("Replaced {count} {occurrences}."));
+    }
+    fn toggle_search_option(&mut self, option: command::System) {
+        let options = &mut self.search_options;
+        match option {
+            ToggleRegex => options.regex = !options.regex,
+            ToggleCaseSensitivity => options.case_sensitivity = options.case_sensitivity.next(),
+            ToggleWholeWord => options.whole_word = !options.whole_word,
+            _ => {}
+        }
+    }
+    // Lists the search options in effect, like " [regex, whole word]".
+    fn search_flags(&self, is_invalid: bool) -> String {
+        let mut labels = self.search_options.labels();
+        if is_invalid {
+            labels.push("invalid");
+        }
+        if labels.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", labels.join(", "))
+        }
+    }
+    fn replace_prompt(&self) -> String {
+        format!("Replace{}: ", self.search_flags(false))
+    }
     fn update_message(&mut self, new_message: &str) {
         self.message_bar.update_message(new_message);
     }
@@ -302,15 +806,33 @@ impl Editor {
         match prompt_type {
             PromptType::None => self.message_bar.set_needs_redraw(true), //Ensures the message bar is properly painted during the next redraw cycle
             PromptType::Save => self.command_bar.set_prompt("Save as: "),
+            PromptType::Replace => self.command_bar.set_prompt(&self.replace_prompt()),
+            PromptType::ReplaceWith => self.command_bar.set_prompt("Replace with: "),
+            PromptType::GoTo => self
+                .command_bar
+                .set_prompt("Go to (line, line:col, +N, -N or N%): "),
+            PromptType::ConfirmReplace => self
+                .command_bar
+                .set_prompt("Replace? (y = yes, n = skip, a = all, q = quit): "),
             PromptType::Search => {
-                self.view.enter_search();
-                self.command_bar
-                    .set_prompt("Search (Esc to cancel, Arrows to navigate): ");
+                self.view_mut().enter_search();
+                self.update_search_prompt();
             }
         }
         self.command_bar.clear_value();
+        if let Some(kind) = prompt_type.history_kind() {
+            self.command_bar.set_history(self.prompt_history.entries(kind));
+        }
         self.prompt_type = prompt_type;
     }
+    // Remembers a value entered into the current prompt.
+    fn add_to_prompt_history(&mut self, value: &str) {
+        if let Some(kind) = self.prompt_type.history_kind() {
+            if let Err(err) = self.prompt_history.add(kind, value) {
+                self.update_message(&format!("ERR: Could not write history: {err}"));
+            }
+        }
+    }
 }
 
 impl Drop for Editor {
@@ -0,0 +1,47 @@
+use std::fmt::{self, Display};
+
+use super::AnnotationType;
+
+// A part of an AnnotatedString which has the same annotation throughout.
+pub struct AnnotatedStringPart<'a> {
+    pub string: &'a str,
+    pub annotation_type: Option<AnnotationType>,
+}
+
+// A string ready to be printed, split into consecutive parts which carry at most one annotation each.
+#[derive(Default)]
+pub struct AnnotatedString {
+    string: String,
+    parts: Vec<(usize, Option<AnnotationType>)>, // (end byte index, annotation) of each part
+}
+
+impl AnnotatedString {
+    pub fn push_str(&mut self, string: &str, annotation_type: Option<AnnotationType>) {
+        self.string.push_str(string);
+        let end = self.string.len();
+        match self.parts.last_mut() {
+            Some((last_end, last_type)) if *last_type == annotation_type => *last_end = end,
+            _ => self.parts.push((end, annotation_type)),
+        }
+    }
+    pub fn push(&mut self, character: char, annotation_type: Option<AnnotationType>) {
+        self.push_str(character.encode_utf8(&mut [0; 4]), annotation_type);
+    }
+    pub fn parts(&self) -> impl Iterator<Item = AnnotatedStringPart<'_>> {
+        let mut start = 0;
+        self.parts.iter().map(move |&(end, annotation_type)| {
+            let string = self.string.get(start..end).unwrap_or_default();
+            start = end;
+            AnnotatedStringPart {
+                string,
+                annotation_type,
+            }
+        })
+    }
+}
+
+impl Display for AnnotatedString {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.string)
+    }
+}
@@ -0,0 +1,31 @@
+pub type ByteIdx = usize;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AnnotationType {
+    Selection,
+    Keyword,
+    Type,
+    Number,
+    String,
+    Comment,
+    Heading,
+    Emphasis,
+    Code,
+    LineNumber,
+    SearchMatch,
+    CurrentSearchMatch,
+}
+
+// Marks the byte range start..end of a line's string with a given type.
+#[derive(Copy, Clone, Debug)]
+pub struct Annotation {
+    pub kind: AnnotationType,
+    pub start: ByteIdx,
+    pub end: ByteIdx,
+}
+
+impl Annotation {
+    pub const fn contains(&self, byte_idx: ByteIdx) -> bool {
+        self.start <= byte_idx && byte_idx < self.end
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    env,
+    io::{Error, ErrorKind, Write},
+    process::{Command, Stdio},
+};
+
+use super::Terminal;
+
+// Shell commands to exchange text with the system clipboard, e.g. `xclip -selection clipboard`
+// for copying and `xclip -selection clipboard -o` for pasting.
+const COPY_COMMAND_VAR: &str = "QUIK_COPY_COMMAND";
+const PASTE_COMMAND_VAR: &str = "QUIK_PASTE_COMMAND";
+// Set to 0 to stop sending copied text to the terminal via OSC 52.
+const OSC52_VAR: &str = "QUIK_OSC52";
+
+// What was last cut or copied. Line-wise content stems from cutting or copying without a selection
+// and is pasted above the current line instead of at the caret.
+#[derive(Clone, Default)]
+pub struct ClipboardContent {
+    pub text: String,
+    pub linewise: bool,
+}
+
+#[derive(Default)]
+pub struct Clipboard {
+    content: Option<ClipboardContent>,
+    copy_command: Option<String>,
+    paste_command: Option<String>,
+    use_osc52: bool,
+}
+
+impl Clipboard {
+    pub fn from_env() -> Self {
+        let non_empty_var = |name| env::var(name).ok().filter(|value| !value.trim().is_empty());
+        Self {
+            content: None,
+            copy_command: non_empty_var(COPY_COMMAND_VAR),
+            paste_command: non_empty_var(PASTE_COMMAND_VAR),
+            use_osc52: env::var(OSC52_VAR).map_or(true, |value| value != "0"),
+        }
+    }
+
+    // Stores the content internally and passes it on to the host clipboard.
+    // The internal copy is kept even if passing it on fails, and the copy command runs even if OSC 52 fails.
+    pub fn set(&mut self, content: ClipboardContent) -> Result<(), Error> {
+        let text = content.text.clone();
+        self.content = Some(content);
+        let osc52_result = if self.use_osc52 {
+            Terminal::copy_to_clipboard(&text)
+        } else {
+            Ok(())
+        };
+        let command_result = self
+            .copy_command
+            .as_ref()
+            .map_or(Ok(()), |command| Self::run_copy_command(command, &text));
+        match (osc52_result, command_result) {
+            (Err(osc52_err), Err(command_err)) => {
+                Err(Error::other(format!("{osc52_err}, {command_err}")))
+            }
+            (result, Ok(())) | (Ok(()), result) => result,
+        }
+    }
+
+    // Retrieves the clipboard content, preferring the output of the paste command if one is configured.
+    pub fn get(&self) -> Result<Option<ClipboardContent>, Error> {
+        let Some(command) = &self.paste_command else {
+            return Ok(self.content.clone());
+        };
+        let text = Self::run_paste_command(command)?;
+        // If the system clipboard still holds what we copied last, keep the line-wise information.
+        if let Some(content) = self.content.as_ref().filter(|content| content.text == text) {
+            return Ok(Some(content.clone()));
+        }
+        Ok(Some(ClipboardContent {
+            text,
+            linewise: false,
+        }))
+    }
+
+    fn shell(command: &str) -> Command {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command).stderr(Stdio::null());
+        shell
+    }
+
+    fn run_copy_command(command: &str, text: &str) -> Result<(), Error> {
+        let mut child = Self::shell(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        // stdin is dropped after writing, which signals the end of input to the command
+        let written = child
+            .stdin
+            .take()
+            .map_or(Ok(()), |mut stdin| stdin.write_all(text.as_bytes()));
+        // The command is waited for even if writing failed, so it doesn't linger as a zombie
+        let status = child.wait()?;
+        written?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::other(format!("copy command exited with {status}")))
+        }
+    }
+
+    fn run_paste_command(command: &str) -> Result<String, Error> {
+        let output = Self::shell(command).stdin(Stdio::null()).output()?;
+        if !output.status.success() {
+            return Err(Error::other(format!(
+                "paste command exited with {}",
+                output.status
+            )));
+        }
+        String::from_utf8(output.stdout)
+            .map(|text| text.replace("\r\n", "\n"))
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process};
+
+    // A stand-in for a clipboard tool, which stores the copied text in a file next to the script.
+    const FAKE_CLIPBOARD: &str = r#"dir=$(dirname "$0")
+case "$1" in
+    copy) cat > "$dir/content" ;;
+    paste) cat "$dir/content" ;;
+    *) exit 1 ;;
+esac
+"#;
+
+    #[test]
+    fn round_trip_through_commands() {
+        let dir = env::temp_dir().join(format!("quik-clipboard-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("fake-clipboard");
+        fs::write(&script, FAKE_CLIPBOARD).unwrap();
+        let script = script.display();
+        // This is the only test touching these variables, so setting them doesn't race with other tests
+        env::set_var(COPY_COMMAND_VAR, format!("sh '{script}' copy"));
+        env::set_var(PASTE_COMMAND_VAR, format!("sh '{script}' paste"));
+        env::set_var(OSC52_VAR, "0");
+        let mut clipboard = Clipboard::from_env();
+
+        clipboard
+            .set(ClipboardContent {
+                text: String::from("first line\nsecond line\n"),
+                linewise: true,
+            })
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("content")).unwrap(),
+            "first line\nsecond line\n"
+        );
+        let content = clipboard.get().unwrap().unwrap();
+        assert_eq!(content.text, "first line\nsecond line\n");
+        assert!(content.linewise);
+
+        // Text copied by another program is taken as it is
+        fs::write(dir.join("content"), "from elsewhere\r\n").unwrap();
+        let content = clipboard.get().unwrap().unwrap();
+        assert_eq!(content.text, "from elsewhere\n");
+        assert!(!content.linewise);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failing_commands_are_reported() {
+        let mut clipboard = Clipboard {
+            copy_command: Some(String::from("exit 3")),
+            paste_command: Some(String::from("exit 4")),
+            ..Clipboard::default()
+        };
+        let content = ClipboardContent {
+            text: String::from("kept"),
+            linewise: false,
+        };
+        assert!(clipboard.set(content).is_err());
+        assert!(clipboard.get().is_err());
+        // The internal copy survives the failing copy command
+        assert_eq!(clipboard.content.unwrap().text, "kept");
+    }
+
+    #[test]
+    fn copy_command_ignoring_its_input_is_waited_for() {
+        let text = "x".repeat(1 << 20); // more than fits into the pipe buffer
+        let result = Clipboard::run_copy_command("exec 0<&-; exit 0", &text);
+        // Writing fails with a broken pipe, which is reported once the command has exited
+        assert!(result.is_err());
+    }
+}
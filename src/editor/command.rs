@@ -1,8 +1,8 @@
 use crossterm::event::{
     Event,
     KeyCode::{
-        self, Backspace, Char, Delete, Down, End, Enter, Home, Left, PageDown, PageUp, Right, Tab,
-        Up,
+        self, BackTab, Backspace, Char, Delete, Down, End, Enter, Home, Left, PageDown, PageUp,
+        Right, Tab, Up,
     },
     KeyEvent, KeyModifiers,
 };
@@ -20,6 +20,12 @@ pub enum Move {
     Left,
     Right,
     Down,
+    WordLeft,
+    WordRight,
+    StartOfBuffer,
+    EndOfBuffer,
+    PreviousParagraph,
+    NextParagraph,
 }
 impl TryFrom<KeyEvent> for Move {
     type Error = String;
@@ -28,7 +34,8 @@ impl TryFrom<KeyEvent> for Move {
             code, modifiers, ..
         } = event;
 
-        if modifiers == KeyModifiers::NONE {
+        // Shift extends the selection, which is decided on when converting into a Command
+        if modifiers == KeyModifiers::NONE || modifiers == KeyModifiers::SHIFT {
             match code {
                 Up => Ok(Self::Up),
                 Down => Ok(Self::Down),
@@ -40,6 +47,18 @@ impl TryFrom<KeyEvent> for Move {
                 End => Ok(Self::EndOfLine),
                 _ => Err(format!("Unsupported code: {code:?}")),
             }
+        } else if modifiers == KeyModifiers::CONTROL
+            || modifiers == KeyModifiers::CONTROL | KeyModifiers::SHIFT
+        {
+            match code {
+                Left => Ok(Self::WordLeft),
+                Right => Ok(Self::WordRight),
+                Home => Ok(Self::StartOfBuffer),
+                End => Ok(Self::EndOfBuffer),
+                Up => Ok(Self::PreviousParagraph),
+                Down => Ok(Self::NextParagraph),
+                _ => Err(format!("Unsupported CONTROL+{code:?} combination")),
+            }
         } else {
             Err(format!(
                 "Unsupported key code {code:?} or modifier {modifiers:?}"
@@ -50,9 +69,15 @@ impl TryFrom<KeyEvent> for Move {
 #[derive(Clone, Copy)]
 pub enum Edit {
     Insert(char),
+    InsertTab,
+    Dedent,
     InsertNewline,
     Delete,
     DeleteBackward,
+    DeleteWord,
+    DeleteWordBackward,
+    Undo,
+    Redo,
 }
 impl TryFrom<KeyEvent> for Edit {
     type Error = String;
@@ -62,10 +87,16 @@ impl TryFrom<KeyEvent> for Edit {
             (Char(character), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
                 Ok(Self::Insert(character))
             }
-            (Tab, KeyModifiers::NONE) => Ok(Self::Insert('\t')),
+            (Tab, KeyModifiers::NONE) => Ok(Self::InsertTab),
+            (BackTab, KeyModifiers::NONE | KeyModifiers::SHIFT) => Ok(Self::Dedent),
             (Enter, KeyModifiers::NONE) => Ok(Self::InsertNewline),
             (Backspace, KeyModifiers::NONE) => Ok(Self::DeleteBackward),
             (Delete, KeyModifiers::NONE) => Ok(Self::Delete),
+            // Ctrl-H is left out, since terminals set to send ^H for Backspace send it for plain Backspace
+            (Backspace, KeyModifiers::CONTROL | KeyModifiers::ALT) => Ok(Self::DeleteWordBackward),
+            (Delete, KeyModifiers::CONTROL) => Ok(Self::DeleteWord),
+            (Char('z'), KeyModifiers::CONTROL) => Ok(Self::Undo),
+            (Char('y'), KeyModifiers::CONTROL) => Ok(Self::Redo),
             _ => Err(format!(
                 "Unsupported key code {:?} with modifiers {:?}",
                 event.code, event.modifiers
@@ -80,7 +111,28 @@ pub enum System {
     Resize(Size),
     Quit,
     Dismiss,
-    Search
+    Search,
+    Cut,
+    Copy,
+    Paste,
+    NextBuffer,
+    PreviousBuffer,
+    ListBuffers,
+    CloseBuffer,
+    SplitHorizontal,
+    SplitVertical,
+    ClosePane,
+    FocusNextPane,
+    FocusPreviousPane,
+    ToggleLineNumbers,
+    ToggleSoftWrap,
+    ToggleRegex,
+    ToggleCaseSensitivity,
+    ToggleWholeWord,
+    Replace,
+    PreviousHistoryEntry,
+    NextHistoryEntry,
+    GoTo,
 }
 
 impl TryFrom<KeyEvent> for System {
@@ -95,8 +147,33 @@ impl TryFrom<KeyEvent> for System {
                 Char('q') => Ok(Self::Quit),
                 Char('s') => Ok(Self::Save),
                 Char('f') => Ok(Self::Search),
+                Char('x') => Ok(Self::Cut),
+                Char('c') => Ok(Self::Copy),
+                Char('v') => Ok(Self::Paste),
+                Char('n') => Ok(Self::NextBuffer),
+                Char('p') => Ok(Self::PreviousBuffer),
+                Char('b') => Ok(Self::ListBuffers),
+                Char('w') => Ok(Self::CloseBuffer),
+                Char('l') => Ok(Self::ToggleLineNumbers),
+                Char('r') => Ok(Self::Replace),
+                Char('g') => Ok(Self::GoTo),
                 _ => Err(format!("Unsupported CONTROL+{code:?} combination")),
             }
+        } else if modifiers == KeyModifiers::ALT {
+            match code {
+                Char('h') => Ok(Self::SplitHorizontal),
+                Char('v') => Ok(Self::SplitVertical),
+                Char('q') => Ok(Self::ClosePane),
+                Char('o') => Ok(Self::FocusNextPane),
+                Char('p') => Ok(Self::FocusPreviousPane),
+                Char('z') => Ok(Self::ToggleSoftWrap),
+                Char('r') => Ok(Self::ToggleRegex),
+                Char('c') => Ok(Self::ToggleCaseSensitivity),
+                Char('w') => Ok(Self::ToggleWholeWord),
+                Up => Ok(Self::PreviousHistoryEntry),
+                Down => Ok(Self::NextHistoryEntry),
+                _ => Err(format!("Unsupported ALT+{code:?} combination")),
+            }
         } else if modifiers == KeyModifiers::NONE && matches!(code, KeyCode::Esc) {
             Ok(Self::Dismiss)
         } else {
@@ -110,6 +187,7 @@ impl TryFrom<KeyEvent> for System {
 #[derive(Clone, Copy)]
 pub enum Command {
     Move(Move),
+    Select(Move),
     Edit(Edit),
     System(System),
 }
@@ -122,7 +200,15 @@ impl TryFrom<Event> for Command {
         match event {
             Event::Key(key_event) => Edit::try_from(key_event)
                 .map(Command::Edit)
-                .or_else(|_| Move::try_from(key_event).map(Command::Move))
+                .or_else(|_| {
+                    Move::try_from(key_event).map(|move_command| {
+                        if key_event.modifiers.contains(KeyModifiers::SHIFT) {
+                            Command::Select(move_command)
+                        } else {
+                            Command::Move(move_command)
+                        }
+                    })
+                })
                 .or_else(|_| System::try_from(key_event).map(Command::System))
                 .map_err(|_err| format!("Event not supported: {key_event:?}")),
             Event::Resize(width_u16, height_u16) => Ok(Self::System(System::Resize(Size {
@@ -1,40 +1,154 @@
 use std::{cmp::min, io::Error};
 
-use super::{command::Edit, Line, Size, Terminal, UIComponent};
+use super::{
+    command::{Edit, Move},
+    Line, Position, Size, Terminal, UIComponent,
+};
+
+const TAB_WIDTH: usize = 1; // tabs in the value are shown as a single space
 
 #[derive(Default)]
 pub struct CommandBar {
     prompt: String,
     value: Line,
+    caret: usize, // the grapheme index of the caret within the value
+    scroll_offset: usize, // the first column of the value that is visible
     needs_redraw: bool,
     size: Size,
+    history: Vec<String>, // the values previously entered into this prompt, oldest first
+    history_idx: Option<usize>, // the history entry currently shown, if any
+    draft: String, // the value typed before browsing the history
 }
 
 impl CommandBar {
     pub fn handle_edit_command(&mut self, command: Edit) {
         match command {
-            Edit::Insert(character) => self.value.append_char(character),
-            Edit::Delete | Edit::InsertNewline => {}
-            Edit::DeleteBackward => self.value.delete_last(),
+            Edit::Insert(character) => self.insert_str(&character.to_string()),
+            Edit::InsertTab => self.insert_str("\t"),
+            Edit::Delete => self.value.delete(self.caret),
+            Edit::DeleteBackward => {
+                if self.caret > 0 {
+                    self.caret = self.caret.saturating_sub(1);
+                    self.value.delete(self.caret);
+                }
+            }
+            Edit::DeleteWord => {
+                let end = self.value.next_word_end(self.caret);
+                self.value.delete_range(self.caret..end);
+            }
+            Edit::DeleteWordBackward => {
+                let start = self.value.previous_word_start(self.caret);
+                self.value.delete_range(start..self.caret);
+                self.caret = start;
+            }
+            Edit::Dedent | Edit::InsertNewline | Edit::Undo | Edit::Redo => {}
         }
+        self.scroll_caret_into_view();
+        self.set_needs_redraw(true);
+    }
+    pub fn handle_move_command(&mut self, command: Move) {
+        self.caret = match command {
+            Move::Left => self.caret.saturating_sub(1),
+            Move::Right => min(self.caret.saturating_add(1), self.value.grapheme_count()),
+            Move::WordLeft => self.value.previous_word_start(self.caret),
+            Move::WordRight => self.value.next_word_end(self.caret),
+            Move::StartOfLine | Move::StartOfBuffer => 0,
+            Move::EndOfLine | Move::EndOfBuffer => self.value.grapheme_count(),
+            Move::Up
+            | Move::Down
+            | Move::PageUp
+            | Move::PageDown
+            | Move::PreviousParagraph
+            | Move::NextParagraph => self.caret,
+        };
+        self.scroll_caret_into_view();
         self.set_needs_redraw(true);
     }
+    // Inserts text at the caret. Line breaks are replaced with spaces, since the value is a single line.
+    pub fn insert_str(&mut self, text: &str) {
+        let text = text.trim_end_matches(['\r', '\n']).replace(['\r', '\n'], " ");
+        let count_before = self.value.grapheme_count();
+        self.value.insert_str(&text, self.caret);
+        // Inserted graphemes may combine with the ones before, so the caret moves by how much the value grew
+        let inserted = self.value.grapheme_count().saturating_sub(count_before);
+        self.caret = self.caret.saturating_add(inserted);
+        self.scroll_caret_into_view();
+        self.set_needs_redraw(true);
+    }
+    fn area_for_value(&self) -> usize {
+        self.size.width.saturating_sub(self.prompt.len())
+    }
+    // Scrolls the value horizontally so that the caret, which needs one column of its own, stays visible.
+    fn scroll_caret_into_view(&mut self) {
+        let caret_col = self.value.width_until(self.caret, TAB_WIDTH);
+        let area = self.area_for_value();
+        if caret_col < self.scroll_offset {
+            self.scroll_offset = caret_col;
+        } else if caret_col >= self.scroll_offset.saturating_add(area) {
+            self.scroll_offset = caret_col.saturating_add(1).saturating_sub(area);
+        }
+    }
     pub fn caret_position_col(&self) -> usize {
-        let max_width = self
-            .prompt
-            .len()
-            .saturating_add(self.value.grapheme_count());
-        min(max_width, self.size.width)
+        let caret_col = self
+            .value
+            .width_until(self.caret, TAB_WIDTH)
+            .saturating_sub(self.scroll_offset);
+        min(
+            self.prompt.len().saturating_add(caret_col),
+            self.size.width.saturating_sub(1),
+        )
     }
     pub fn value(&self) -> String {
         self.value.to_string()
     }
     pub fn set_prompt(&mut self, prompt: &str) {
         self.prompt = prompt.to_string();
+        self.scroll_caret_into_view();
         self.set_needs_redraw(true);
     }
     pub fn clear_value(&mut self) {
         self.value = Line::default();
+        self.caret = 0;
+        self.scroll_offset = 0;
+        self.history_idx = None;
+        self.set_needs_redraw(true);
+    }
+    pub fn set_history(&mut self, history: &[String]) {
+        self.history = history.to_vec();
+        self.history_idx = None;
+    }
+    // Replaces the value with the previous (older) history entry.
+    // Returns false if there is none, leaving the value untouched.
+    pub fn previous_history_entry(&mut self) -> bool {
+        let idx = match self.history_idx {
+            None if !self.history.is_empty() => {
+                self.draft = self.value();
+                self.history.len().saturating_sub(1)
+            }
+            Some(idx) if idx > 0 => idx.saturating_sub(1),
+            _ => return false,
+        };
+        self.show_history_entry(Some(idx));
+        true
+    }
+    // Replaces the value with the next (newer) history entry, or the draft after the newest one.
+    // Returns false if the history isn't being browsed.
+    pub fn next_history_entry(&mut self) -> bool {
+        let Some(idx) = self.history_idx else {
+            return false;
+        };
+        let next = idx.saturating_add(1);
+        self.show_history_entry((next < self.history.len()).then_some(next));
+        true
+    }
+    fn show_history_entry(&mut self, idx: Option<usize>) {
+        let value = idx
+            .and_then(|idx| self.history.get(idx))
+            .unwrap_or(&self.draft);
+        self.value = Line::from(value);
+        self.caret = self.value.grapheme_count();
+        self.history_idx = idx;
+        self.scroll_caret_into_view();
         self.set_needs_redraw(true);
     }
 }
@@ -48,21 +162,174 @@ impl UIComponent for CommandBar {
     }
     fn set_size(&mut self, size: Size) {
         self.size = size;
+        self.scroll_caret_into_view();
     }
-    fn draw(&mut self, origin: usize) -> Result<(), Error> {
-        let area_for_value = self.size.width.saturating_sub(self.prompt.len()); //this is how much space there is between the right side of the prompt and the edge of the bar
-        let value_end = self.value.width(); // we always want to show the left part of the value, therefore the end of the visible range we try to access will be equal to the full width
-        let value_start = value_end.saturating_sub(area_for_value); //This should give us the start for the grapheme subrange we want to print out.
-        let message = format!(
-            "{}{}",
-            self.prompt,
-            self.value.get_visible_graphemes(value_start..value_end)
-        );
-        let to_print = if message.len() <= self.size.width {
-            message
+    fn draw(&mut self, origin: Position) -> Result<(), Error> {
+        let area = self.area_for_value();
+        let visible = self
+            .value
+            .get_visible_graphemes(
+                self.scroll_offset..self.scroll_offset.saturating_add(area),
+                TAB_WIDTH,
+            );
+        let to_print = if self.prompt.len() < self.size.width {
+            format!("{}{visible}", self.prompt)
         } else {
             String::new()
         };
-        Terminal::print_row(origin, &to_print)
+        Terminal::print_row(origin.row, &to_print)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_value(prompt: &str, width: usize, value: &str) -> CommandBar {
+        let mut command_bar = CommandBar::default();
+        command_bar.resize(Size { height: 1, width });
+        command_bar.set_prompt(prompt);
+        command_bar.insert_str(value);
+        command_bar
+    }
+
+    fn edit(command_bar: &mut CommandBar, command: Edit) -> (String, usize) {
+        command_bar.handle_edit_command(command);
+        (command_bar.value(), command_bar.caret)
+    }
+
+    #[test]
+    fn moves_the_caret() {
+        let mut command_bar = with_value("> ", 20, "foo bar");
+        let mut move_caret = |command| {
+            command_bar.handle_move_command(command);
+            command_bar.caret
+        };
+        assert_eq!(move_caret(Move::Right), 7);
+        assert_eq!(move_caret(Move::Left), 6);
+        assert_eq!(move_caret(Move::WordLeft), 4);
+        assert_eq!(move_caret(Move::WordLeft), 0);
+        assert_eq!(move_caret(Move::Left), 0);
+        assert_eq!(move_caret(Move::WordLeft), 0);
+        assert_eq!(move_caret(Move::WordRight), 3);
+        assert_eq!(move_caret(Move::Up), 3);
+        assert_eq!(move_caret(Move::EndOfLine), 7);
+        assert_eq!(move_caret(Move::WordRight), 7);
+        assert_eq!(move_caret(Move::StartOfBuffer), 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn deletes_words_up_to_the_edges() {
+        let mut command_bar = with_value("> ", 20, "foo bar");
+        assert_eq!(
+            edit(&mut command_bar, Edit::DeleteWord),
+            (String::from("foo bar"), 7)
+        );
+        assert_eq!(
+            edit(&mut command_bar, Edit::Delete),
+            (String::from("foo bar"), 7)
+        );
+        assert_eq!(
+            edit(&mut command_bar, Edit::DeleteWordBackward),
+            (String::from("foo "), 4)
+        );
+        command_bar.handle_move_command(Move::StartOfLine);
+        assert_eq!(
+            edit(&mut command_bar, Edit::DeleteWordBackward),
+            (String::from("foo "), 0)
+        );
+        assert_eq!(
+            edit(&mut command_bar, Edit::DeleteBackward),
+            (String::from("foo "), 0)
+        );
+        assert_eq!(
+            edit(&mut command_bar, Edit::DeleteWord),
+            (String::from(" "), 0)
+        );
+        assert_eq!(edit(&mut command_bar, Edit::DeleteWord), (String::new(), 0));
+    }
+
+    #[test]
+    fn inserts_text_as_a_single_line() {
+        let mut command_bar = with_value("> ", 20, "a\r\nb\n");
+        assert_eq!(
+            (command_bar.value(), command_bar.caret),
+            (String::from("a  b"), 4)
+        );
+        command_bar.handle_move_command(Move::Left);
+        command_bar.insert_str("x\ny");
+        assert_eq!(
+            (command_bar.value(), command_bar.caret),
+            (String::from("a  x yb"), 6)
+        );
+
+        // A combining mark joins the grapheme before the caret instead of adding one
+        let mut command_bar = with_value("> ", 20, "e");
+        command_bar.insert_str("\u{301}");
+        assert_eq!(
+            (command_bar.value(), command_bar.caret),
+            (String::from("e\u{301}"), 1)
+        );
+        assert_eq!(
+            edit(&mut command_bar, Edit::Insert('!')),
+            (String::from("e\u{301}!"), 2)
+        );
+    }
+
+    #[test]
+    fn scrolls_to_keep_the_caret_visible() {
+        // The prompt leaves 8 columns for the value, the last one is taken by the caret at the end
+        let mut command_bar = with_value("> ", 10, "abcdefghij");
+        assert_eq!(command_bar.scroll_offset, 3);
+        assert_eq!(command_bar.caret_position_col(), 9);
+        command_bar.handle_move_command(Move::WordLeft);
+        assert_eq!(command_bar.scroll_offset, 0);
+        assert_eq!(command_bar.caret_position_col(), 2);
+        command_bar.handle_move_command(Move::EndOfLine);
+        assert_eq!(command_bar.caret_position_col(), 9);
+
+        // Wide graphemes take up two columns, tabs one
+        let mut command_bar = with_value("> ", 10, "日本語\t");
+        assert_eq!(command_bar.scroll_offset, 0);
+        assert_eq!(command_bar.caret_position_col(), 9);
+        command_bar.insert_str("x");
+        assert_eq!(command_bar.scroll_offset, 1);
+        assert_eq!(command_bar.caret_position_col(), 9);
+
+        // A prompt wider than the bar leaves the caret in the last column
+        let command_bar = with_value("Search: ", 5, "abc");
+        assert_eq!(command_bar.caret_position_col(), 4);
+    }
+
+    #[test]
+    fn browses_the_history_and_returns_to_the_draft() {
+        let mut command_bar = with_value("> ", 20, "dra");
+        assert!(!command_bar.previous_history_entry());
+        command_bar.set_history(&[String::from("one"), String::from("two")]);
+        assert!(!command_bar.next_history_entry());
+        assert!(command_bar.previous_history_entry());
+        assert_eq!(
+            (command_bar.value(), command_bar.caret),
+            (String::from("two"), 3)
+        );
+        assert!(command_bar.previous_history_entry());
+        assert_eq!(command_bar.value(), "one");
+        assert!(!command_bar.previous_history_entry());
+        assert_eq!(command_bar.value(), "one");
+        assert!(command_bar.next_history_entry());
+        assert_eq!(command_bar.value(), "two");
+        assert!(command_bar.next_history_entry());
+        assert_eq!(
+            (command_bar.value(), command_bar.caret),
+            (String::from("dra"), 3)
+        );
+        assert!(!command_bar.next_history_entry());
+
+        // Clearing the value stops browsing, so the next step back starts at the newest entry again
+        assert!(command_bar.previous_history_entry());
+        command_bar.clear_value();
+        assert!(!command_bar.next_history_entry());
+        assert!(command_bar.previous_history_entry());
+        assert_eq!(command_bar.value(), "two");
+    }
+}
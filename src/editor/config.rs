@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    env,
+    fs::read_to_string,
+    io::{Error, ErrorKind},
+    path::PathBuf,
+};
+
+use super::{FileType, FileTypeSettings};
+
+const CONFIG_PATH_VAR: &str = "QUIK_CONFIG";
+
+/// User configuration, read from `$QUIK_CONFIG`, `$XDG_CONFIG_HOME/quik/config`
+/// or `~/.config/quik/config`, in this order.
+///
+/// The file consists of one section per file type, holding the settings to override:
+/// ```text
+/// # Comments start with a hash
+/// [python]
+/// indent_width = 2
+/// use_tabs = false
+/// comment_token = #
+/// ```
+#[derive(Default)]
+pub struct Config {
+    file_type_settings: HashMap<FileType, FileTypeSettings>,
+}
+
+impl Config {
+    // Loads the configuration file. A missing file is not an error and yields the default configuration.
+    pub fn load() -> Result<Self, Error> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        match read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os(CONFIG_PATH_VAR) {
+            return Some(PathBuf::from(path));
+        }
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|dir| dir.join("quik").join("config"))
+    }
+
+    fn parse(contents: &str) -> Result<Self, Error> {
+        let mut config = Self::default();
+        let mut current: Option<FileType> = None;
+        for (line_idx, line) in contents.lines().enumerate() {
+            let line_nr = line_idx.saturating_add(1);
+            let invalid =
+                |reason: &str| Error::new(ErrorKind::InvalidData, format!("line {line_nr}: {reason}"));
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                let file_type = FileType::from_name(section.trim())
+                    .ok_or_else(|| invalid(&format!("unknown file type '{}'", section.trim())))?;
+                current = Some(file_type);
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| invalid("expected 'key = value'"))?;
+            let file_type = current.ok_or_else(|| invalid("setting outside of a [file type] section"))?;
+            let settings = config
+                .file_type_settings
+                .entry(file_type)
+                .or_insert_with(|| file_type.default_settings());
+            match key {
+                "indent_width" => {
+                    settings.indent_width = value
+                        .parse()
+                        .ok()
+                        .filter(|width| *width > 0)
+                        .ok_or_else(|| invalid("indent_width must be a positive number"))?;
+                }
+                "tab_width" => {
+                    settings.tab_width = value
+                        .parse()
+                        .ok()
+                        .filter(|width| *width > 0)
+                        .ok_or_else(|| invalid("tab_width must be a positive number"))?;
+                }
+                "use_tabs" => {
+                    settings.use_tabs = value
+                        .parse()
+                        .map_err(|_| invalid("use_tabs must be true or false"))?;
+                }
+                "comment_token" => {
+                    settings.comment_token = Some(value.to_string()).filter(|token| !token.is_empty());
+                }
+                _ => return Err(invalid(&format!("unknown setting '{key}'"))),
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn settings_for(&self, file_type: FileType) -> FileTypeSettings {
+        self.file_type_settings
+            .get(&file_type)
+            .cloned()
+            .unwrap_or_else(|| file_type.default_settings())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(contents: &str) -> String {
+        Config::parse(contents).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn overrides_settings_per_file_type() {
+        let config = Config::parse(
+            "# comment\n\n[python]\nindent_width = 2\nuse_tabs=true\n  tab_width = 8  \n\
+             [Rust]\ncomment_token =\n",
+        )
+        .unwrap();
+        let python = config.settings_for(FileType::Python);
+        assert_eq!(
+            (python.indent_width, python.use_tabs, python.tab_width),
+            (2, true, 8)
+        );
+        assert_eq!(python.comment_token.as_deref(), Some("#"));
+        assert_eq!(config.settings_for(FileType::Rust).comment_token, None);
+        assert_eq!(
+            config.settings_for(FileType::C),
+            FileType::C.default_settings()
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        assert_eq!(parse_error("[cobol]"), "line 1: unknown file type 'cobol'");
+        assert_eq!(
+            parse_error("indent_width = 2"),
+            "line 1: setting outside of a [file type] section"
+        );
+        assert_eq!(
+            parse_error("[c]\nindent_width"),
+            "line 2: expected 'key = value'"
+        );
+        assert_eq!(
+            parse_error("[c]\n\nindent_width = 0"),
+            "line 3: indent_width must be a positive number"
+        );
+        assert_eq!(
+            parse_error("[c]\nuse_tabs = yes"),
+            "line 2: use_tabs must be true or false"
+        );
+        assert_eq!(
+            parse_error("[c]\ncolor = red"),
+            "line 2: unknown setting 'color'"
+        );
+    }
+}
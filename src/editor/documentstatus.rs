@@ -1,15 +1,22 @@
+use super::{FileType, FileTypeSettings};
+
 #[derive(Default, Eq, PartialEq, Debug)]
 pub struct DocumentStatus {
     pub total_lines: usize,
     pub current_line_idx: usize,
     pub is_modified: bool,
+    pub is_readonly: bool,
     pub file_name: String,
+    pub file_type: FileType,
+    pub settings: FileTypeSettings,
 }
 
 impl DocumentStatus {
     pub fn modified_indicator_to_string(&self) -> String {
         if self.is_modified {
             String::from("(modified)")
+        } else if self.is_readonly {
+            String::from("(read-only)")
         } else {
             String::new()
         }
@@ -17,6 +24,14 @@ impl DocumentStatus {
     pub fn line_count_to_string(&self) -> String {
         format!("{} lines", self.total_lines)
     }
+    pub fn file_type_to_string(&self) -> String {
+        let indentation = if self.settings.use_tabs {
+            format!("tabs: {}", self.settings.tab_width)
+        } else {
+            format!("spaces: {}", self.settings.indent_width)
+        };
+        format!("{} | {indentation}", self.file_type)
+    }
     pub fn position_indicator_to_string(&self) -> String {
         format!(
             "{}/{}",
@@ -0,0 +1,162 @@
+use std::fmt::{self, Display};
+
+#[derive(Copy, Clone, Default, Eq, PartialEq, Hash, Debug)]
+pub enum FileType {
+    Rust,
+    C,
+    Python,
+    Markdown,
+    #[default]
+    Text,
+}
+
+impl FileType {
+    const ALL: [Self; 5] = [
+        Self::Rust,
+        Self::C,
+        Self::Python,
+        Self::Markdown,
+        Self::Text,
+    ];
+
+    pub const fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Rust => &["rs"],
+            Self::C => &["c", "h"],
+            Self::Python => &["py", "pyw"],
+            Self::Markdown => &["md", "markdown"],
+            Self::Text => &["txt"],
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let extension = extension.to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|file_type| file_type.extensions().contains(&extension.as_str()))
+    }
+
+    // Accepts the names used in modelines, in shebang lines and in the config file.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "rust" => Some(Self::Rust),
+            "c" => Some(Self::C),
+            "python" => Some(Self::Python),
+            "markdown" => Some(Self::Markdown),
+            "text" | "plain" => Some(Self::Text),
+            other => Self::from_extension(other),
+        }
+    }
+
+    // Whether a line ending in the given text opens a block, so the next line is indented one level deeper.
+    pub fn opens_block(self, text: &str) -> bool {
+        let text = text.trim_end();
+        match self {
+            Self::Rust | Self::C => text.ends_with(['{', '(', '[']),
+            Self::Python => text.ends_with([':', '{', '(', '[']),
+            Self::Markdown | Self::Text => false,
+        }
+    }
+    // Whether typing the given character on an otherwise blank line closes a block, and should dedent it.
+    pub const fn closes_block(self, character: char) -> bool {
+        match self {
+            Self::Rust | Self::C | Self::Python => matches!(character, '}' | ')' | ']'),
+            Self::Markdown | Self::Text => false,
+        }
+    }
+
+    pub fn default_settings(self) -> FileTypeSettings {
+        let (comment_token, indent_width, use_tabs) = match self {
+            Self::Rust | Self::C => (Some("//"), 4, false),
+            Self::Python => (Some("#"), 4, false),
+            Self::Markdown => (None, 2, false),
+            Self::Text => (None, 4, true),
+        };
+        FileTypeSettings {
+            comment_token: comment_token.map(String::from),
+            indent_width,
+            use_tabs,
+            tab_width: 4,
+        }
+    }
+}
+
+impl Display for FileType {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Rust => "Rust",
+            Self::C => "C",
+            Self::Python => "Python",
+            Self::Markdown => "Markdown",
+            Self::Text => "Text",
+        };
+        write!(formatter, "{name}")
+    }
+}
+
+// Settings which depend on the type of the file being edited.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FileTypeSettings {
+    pub comment_token: Option<String>,
+    pub indent_width: usize,
+    pub use_tabs: bool,
+    pub tab_width: usize, // the number of columns between tab stops
+}
+
+impl FileTypeSettings {
+    // The whitespace added by one level of indentation.
+    pub fn indent_unit(&self) -> String {
+        if self.use_tabs {
+            String::from("\t")
+        } else {
+            " ".repeat(self.indent_width)
+        }
+    }
+}
+
+impl Default for FileTypeSettings {
+    fn default() -> Self {
+        FileType::default().default_settings()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brackets_open_and_close_blocks_in_rust_and_c() {
+        for file_type in [FileType::Rust, FileType::C] {
+            assert!(file_type.opens_block("fn main() {"));
+            assert!(file_type.opens_block("    call(  "));
+            assert!(file_type.opens_block("let v = ["));
+            assert!(!file_type.opens_block("let x = 1;"));
+            assert!(!file_type.opens_block("label:"));
+            assert!(!file_type.opens_block(""));
+            for character in ['}', ')', ']'] {
+                assert!(file_type.closes_block(character));
+            }
+            assert!(!file_type.closes_block(':'));
+            assert!(!file_type.closes_block('x'));
+        }
+    }
+
+    #[test]
+    fn colons_also_open_blocks_in_python() {
+        assert!(FileType::Python.opens_block("def f():"));
+        assert!(FileType::Python.opens_block("if x:  "));
+        assert!(FileType::Python.opens_block("d = {"));
+        assert!(!FileType::Python.opens_block("x = 1"));
+        assert!(FileType::Python.closes_block(']'));
+        assert!(!FileType::Python.closes_block(':'));
+    }
+
+    #[test]
+    fn prose_has_no_blocks() {
+        for file_type in [FileType::Markdown, FileType::Text] {
+            assert!(!file_type.opens_block("- item {"));
+            assert!(!file_type.opens_block("Note:"));
+            assert!(!file_type.closes_block('}'));
+        }
+    }
+}
@@ -0,0 +1,526 @@
+use std::{io::Error, mem};
+
+use super::{Position, Size, Terminal, UIComponent, View};
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum SplitDirection {
+    Horizontal, // panes stacked on top of each other
+    Vertical,   // panes side by side
+}
+
+// A leaf of the layout tree. The origin is relative to the layout's origin.
+#[derive(Default)]
+pub struct Pane {
+    pub view: View,
+    origin: Position,
+}
+
+enum Node {
+    Pane(Box<Pane>),
+    Split {
+        direction: SplitDirection,
+        children: Vec<Node>,
+    },
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Self::Pane(Box::default())
+    }
+}
+
+// The line between two panes. The origin is relative to the layout's origin.
+struct Separator {
+    origin: Position,
+    length: usize,
+    direction: SplitDirection,
+}
+
+// Arranges the views in a tree of splits. Panes are addressed by their index in depth-first order,
+// which is also the order in which focus cycles through them.
+#[derive(Default)]
+pub struct Layout {
+    root: Node,
+    focused: usize,
+    separators: Vec<Separator>,
+    needs_redraw: bool,
+    size: Size,
+}
+
+impl Layout {
+    pub fn new(view: View) -> Self {
+        Self {
+            root: Node::Pane(Box::new(Pane {
+                view,
+                origin: Position::default(),
+            })),
+            ..Self::default()
+        }
+    }
+
+    pub fn panes(&self) -> Vec<&Pane> {
+        let mut panes = Vec::new();
+        self.root.collect_panes(&mut panes);
+        panes
+    }
+    pub fn panes_mut(&mut self) -> Vec<&mut Pane> {
+        let mut panes = Vec::new();
+        self.root.collect_panes_mut(&mut panes);
+        panes
+    }
+    fn focused_pane(&self) -> Option<&Pane> {
+        self.panes().into_iter().nth(self.focused)
+    }
+    pub fn focused_view(&self) -> Option<&View> {
+        self.focused_pane().map(|pane| &pane.view)
+    }
+    pub fn focused_view_mut(&mut self) -> Option<&mut View> {
+        let focused = self.focused;
+        self.panes_mut()
+            .into_iter()
+            .nth(focused)
+            .map(|pane| &mut pane.view)
+    }
+
+    // Splits the focused pane in two, both showing the same buffer, and focuses the new pane.
+    // Returns false if the focused pane is too small to be split.
+    pub fn split(&mut self, direction: SplitDirection) -> bool {
+        let Some(pane) = self.focused_pane() else {
+            return false;
+        };
+        let size = pane.view.size();
+        let available = match direction {
+            SplitDirection::Horizontal => size.height,
+            SplitDirection::Vertical => size.width,
+        };
+        // Each half needs at least one row or column, plus one for the separator in between
+        if available < 3 {
+            return false;
+        }
+        self.root.split(self.focused, direction);
+        self.focused = self.focused.saturating_add(1);
+        self.resize(self.size);
+        true
+    }
+    // Closes the focused pane and focuses its predecessor.
+    // Returns false if the focused pane is the last one, which can't be closed.
+    pub fn close_focused(&mut self) -> bool {
+        if self.panes().len() <= 1 {
+            return false;
+        }
+        self.root.close(self.focused);
+        self.focused = self.focused.saturating_sub(1);
+        self.resize(self.size);
+        true
+    }
+    pub fn focus_next(&mut self) {
+        let next = self.focused.saturating_add(1);
+        self.focused = if next < self.panes().len() { next } else { 0 };
+    }
+    pub fn focus_previous(&mut self) {
+        self.focused = self
+            .focused
+            .checked_sub(1)
+            .unwrap_or_else(|| self.panes().len().saturating_sub(1));
+    }
+
+    // The position of the caret of the focused view, relative to the layout's origin.
+    pub fn caret_position(&self) -> Position {
+        self.focused_pane().map_or_else(Position::default, |pane| {
+            let caret = pane.view.caret_position();
+            Position {
+                row: pane.origin.row.saturating_add(caret.row),
+                col: pane.origin.col.saturating_add(caret.col),
+            }
+        })
+    }
+}
+
+impl Node {
+    fn collect_panes<'a>(&'a self, panes: &mut Vec<&'a Pane>) {
+        match self {
+            Self::Pane(pane) => panes.push(pane),
+            Self::Split { children, .. } => {
+                for child in children {
+                    child.collect_panes(panes);
+                }
+            }
+        }
+    }
+    fn collect_panes_mut<'a>(&'a mut self, panes: &mut Vec<&'a mut Pane>) {
+        match self {
+            Self::Pane(pane) => panes.push(pane),
+            Self::Split { children, .. } => {
+                for child in children {
+                    child.collect_panes_mut(panes);
+                }
+            }
+        }
+    }
+    fn pane_count(&self) -> usize {
+        match self {
+            Self::Pane(_) => 1,
+            Self::Split { children, .. } => children.iter().map(Self::pane_count).sum(),
+        }
+    }
+
+    // Finds the child containing the pane with the given index.
+    // Returns the index of that child and the index of its first pane.
+    fn child_containing(children: &[Self], pane_idx: usize) -> Option<(usize, usize)> {
+        let mut first_idx: usize = 0;
+        for (child_idx, child) in children.iter().enumerate() {
+            let count = child.pane_count();
+            if pane_idx < first_idx.saturating_add(count) {
+                return Some((child_idx, first_idx));
+            }
+            first_idx = first_idx.saturating_add(count);
+        }
+        None
+    }
+
+    // Splits the pane with the given index within this node.
+    // A split in the same direction as the surrounding one just adds another pane to it.
+    fn split(&mut self, pane_idx: usize, direction: SplitDirection) {
+        match self {
+            Self::Pane(pane) => {
+                let new_pane = Self::Pane(Box::new(Pane {
+                    view: pane.view.duplicate(),
+                    origin: pane.origin,
+                }));
+                let old_pane = mem::replace(
+                    self,
+                    Self::Split {
+                        direction,
+                        children: Vec::new(),
+                    },
+                );
+                if let Self::Split { children, .. } = self {
+                    children.push(old_pane);
+                    children.push(new_pane);
+                }
+            }
+            Self::Split {
+                direction: own_direction,
+                children,
+            } => {
+                let Some((child_idx, first_idx)) = Self::child_containing(children, pane_idx) else {
+                    return;
+                };
+                match children.get_mut(child_idx) {
+                    Some(Self::Pane(pane)) if *own_direction == direction => {
+                        let new_pane = Self::Pane(Box::new(Pane {
+                            view: pane.view.duplicate(),
+                            origin: pane.origin,
+                        }));
+                        children.insert(child_idx.saturating_add(1), new_pane);
+                    }
+                    Some(child) => child.split(pane_idx.saturating_sub(first_idx), direction),
+                    None => {}
+                }
+            }
+        }
+    }
+    // Removes the pane with the given index within this node, which must be a split.
+    // Splits left with a single child are replaced by that child.
+    fn close(&mut self, pane_idx: usize) {
+        let Self::Split {
+            direction,
+            children,
+        } = self
+        else {
+            return;
+        };
+        let Some((child_idx, first_idx)) = Self::child_containing(children, pane_idx) else {
+            return;
+        };
+        match children.get_mut(child_idx) {
+            Some(Self::Pane(_)) => {
+                children.remove(child_idx);
+            }
+            Some(child) => {
+                child.close(pane_idx.saturating_sub(first_idx));
+                // A collapsed child split in the same direction is merged into this one
+                if let Self::Split {
+                    direction: child_direction,
+                    children: grandchildren,
+                } = child
+                {
+                    if child_direction == direction {
+                        let grandchildren = mem::take(grandchildren);
+                        children.splice(child_idx..=child_idx, grandchildren);
+                    }
+                }
+            }
+            None => {}
+        }
+        if children.len() == 1 {
+            if let Some(child) = children.pop() {
+                *self = child;
+            }
+        }
+    }
+
+    // Distributes the given area evenly among the children, leaving room for the separators between them.
+    fn arrange(&mut self, origin: Position, size: Size, separators: &mut Vec<Separator>) {
+        match self {
+            Self::Pane(pane) => {
+                pane.origin = origin;
+                pane.view.resize(size);
+            }
+            Self::Split {
+                direction,
+                children,
+            } => {
+                let count = children.len();
+                let total = match direction {
+                    SplitDirection::Horizontal => size.height,
+                    SplitDirection::Vertical => size.width,
+                };
+                let available = total.saturating_sub(count.saturating_sub(1));
+                let share = available.checked_div(count).unwrap_or(0);
+                let remainder = available.checked_rem(count).unwrap_or(0);
+                let mut offset = 0;
+                for (idx, child) in children.iter_mut().enumerate() {
+                    // The first panes get one more row or column each, until the remainder is used up
+                    let length = if idx < remainder {
+                        share.saturating_add(1)
+                    } else {
+                        share
+                    };
+                    let (child_origin, child_size, separator_origin) = match direction {
+                        SplitDirection::Horizontal => (
+                            Position {
+                                row: origin.row.saturating_add(offset),
+                                col: origin.col,
+                            },
+                            Size {
+                                height: length,
+                                width: size.width,
+                            },
+                            Position {
+                                row: origin.row.saturating_add(offset).saturating_add(length),
+                                col: origin.col,
+                            },
+                        ),
+                        SplitDirection::Vertical => (
+                            Position {
+                                row: origin.row,
+                                col: origin.col.saturating_add(offset),
+                            },
+                            Size {
+                                height: size.height,
+                                width: length,
+                            },
+                            Position {
+                                row: origin.row,
+                                col: origin.col.saturating_add(offset).saturating_add(length),
+                            },
+                        ),
+                    };
+                    child.arrange(child_origin, child_size, separators);
+                    if idx.saturating_add(1) < count {
+                        separators.push(Separator {
+                            origin: separator_origin,
+                            length: match direction {
+                                SplitDirection::Horizontal => size.width,
+                                SplitDirection::Vertical => size.height,
+                            },
+                            direction: *direction,
+                        });
+                    }
+                    offset = offset.saturating_add(length).saturating_add(1);
+                }
+            }
+        }
+    }
+}
+
+impl UIComponent for Layout {
+    fn set_needs_redraw(&mut self, value: bool) {
+        self.needs_redraw = value;
+        if value {
+            for pane in self.panes_mut() {
+                pane.view.set_needs_redraw(true);
+            }
+        }
+    }
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw || self.panes().iter().any(|pane| pane.view.needs_redraw())
+    }
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
+        let mut separators = Vec::new();
+        self.root.arrange(Position::default(), size, &mut separators);
+        self.separators = separators;
+    }
+    fn draw(&mut self, origin: Position) -> Result<(), Error> {
+        for pane in self.panes_mut() {
+            pane.view.render(Position {
+                row: origin.row.saturating_add(pane.origin.row),
+                col: origin.col.saturating_add(pane.origin.col),
+            });
+        }
+        if self.needs_redraw {
+            for separator in &self.separators {
+                let at = Position {
+                    row: origin.row.saturating_add(separator.origin.row),
+                    col: origin.col.saturating_add(separator.origin.col),
+                };
+                match separator.direction {
+                    SplitDirection::Horizontal => {
+                        Terminal::print_at(at, &"─".repeat(separator.length))?;
+                    }
+                    SplitDirection::Vertical => {
+                        for row in 0..separator.length {
+                            Terminal::print_at(
+                                Position {
+                                    row: at.row.saturating_add(row),
+                                    col: at.col,
+                                },
+                                "│",
+                            )?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(height: usize, width: usize) -> Layout {
+        let mut layout = Layout::new(View::default());
+        layout.resize(Size { height, width });
+        layout
+    }
+
+    // The tree of splits, like "V[P, H[P, P]]"
+    fn shape(node: &Node) -> String {
+        match node {
+            Node::Pane(_) => String::from("P"),
+            Node::Split {
+                direction,
+                children,
+            } => {
+                let direction = match direction {
+                    SplitDirection::Horizontal => "H",
+                    SplitDirection::Vertical => "V",
+                };
+                let children: Vec<_> = children.iter().map(shape).collect();
+                format!("{direction}[{}]", children.join(", "))
+            }
+        }
+    }
+
+    // The row, column, height and width of each pane, in depth-first order
+    fn panes(layout: &Layout) -> Vec<(usize, usize, usize, usize)> {
+        layout
+            .panes()
+            .iter()
+            .map(|pane| {
+                let size = pane.view.size();
+                (pane.origin.row, pane.origin.col, size.height, size.width)
+            })
+            .collect()
+    }
+
+    // The row, column and length of each separator, and whether it's a vertical line
+    fn separators(layout: &Layout) -> Vec<(usize, usize, usize, bool)> {
+        layout
+            .separators
+            .iter()
+            .map(|separator| {
+                (
+                    separator.origin.row,
+                    separator.origin.col,
+                    separator.length,
+                    separator.direction == SplitDirection::Vertical,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn splits_the_focused_pane() {
+        let mut layout = layout(11, 20);
+        assert!(layout.split(SplitDirection::Vertical));
+        assert_eq!(shape(&layout.root), "V[P, P]");
+        assert_eq!(layout.focused, 1);
+        // The first pane gets the column left over
+        assert_eq!(panes(&layout), [(0, 0, 11, 10), (0, 11, 11, 9)]);
+        assert_eq!(separators(&layout), [(0, 10, 11, true)]);
+
+        assert!(layout.split(SplitDirection::Horizontal));
+        assert_eq!(shape(&layout.root), "V[P, H[P, P]]");
+        assert_eq!(layout.focused, 2);
+        assert_eq!(
+            panes(&layout),
+            [(0, 0, 11, 10), (0, 11, 5, 9), (6, 11, 5, 9)]
+        );
+        assert_eq!(separators(&layout), [(0, 10, 11, true), (5, 11, 9, false)]);
+
+        // Splitting in the direction of the surrounding split adds a sibling
+        layout.focused = 0;
+        assert!(layout.split(SplitDirection::Vertical));
+        assert_eq!(shape(&layout.root), "V[P, P, H[P, P]]");
+        assert_eq!(layout.focused, 1);
+        assert_eq!(
+            panes(&layout),
+            [(0, 0, 11, 6), (0, 7, 11, 6), (0, 14, 5, 6), (6, 14, 5, 6)]
+        );
+    }
+
+    #[test]
+    fn refuses_to_split_small_panes() {
+        let mut layout = layout(2, 3);
+        assert!(!layout.split(SplitDirection::Horizontal));
+        assert!(layout.split(SplitDirection::Vertical));
+        assert!(!layout.split(SplitDirection::Vertical));
+        assert_eq!(panes(&layout), [(0, 0, 2, 1), (0, 2, 2, 1)]);
+    }
+
+    #[test]
+    fn closing_collapses_and_merges_splits() {
+        let mut layout = layout(11, 20);
+        layout.split(SplitDirection::Vertical);
+        layout.split(SplitDirection::Horizontal);
+        layout.split(SplitDirection::Vertical);
+        assert_eq!(shape(&layout.root), "V[P, H[P, V[P, P]]]");
+        assert_eq!(layout.focused, 3);
+
+        // Closing the other pane of the horizontal split leaves a vertical split within a vertical one
+        layout.focused = 1;
+        assert!(layout.close_focused());
+        assert_eq!(shape(&layout.root), "V[P, P, P]");
+        assert_eq!(layout.focused, 0);
+        assert_eq!(
+            panes(&layout),
+            [(0, 0, 11, 6), (0, 7, 11, 6), (0, 14, 11, 6)]
+        );
+        assert_eq!(separators(&layout), [(0, 6, 11, true), (0, 13, 11, true)]);
+
+        assert!(layout.close_focused());
+        assert!(layout.close_focused());
+        assert_eq!(shape(&layout.root), "P");
+        assert_eq!(panes(&layout), [(0, 0, 11, 20)]);
+        assert!(separators(&layout).is_empty());
+        assert!(!layout.close_focused());
+    }
+
+    #[test]
+    fn focus_cycles_through_the_panes() {
+        let mut layout = layout(11, 20);
+        layout.split(SplitDirection::Vertical);
+        layout.split(SplitDirection::Horizontal);
+        layout.focus_next();
+        assert_eq!(layout.focused, 0);
+        layout.focus_previous();
+        assert_eq!(layout.focused, 2);
+        layout.focus_previous();
+        assert_eq!(layout.focused, 1);
+    }
+}
@@ -1,23 +1,33 @@
 use std::{
+    cmp::{max, min},
     fmt,
     ops::{Deref, Range},
 };
+use regex::Regex;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
+use super::{AnnotatedString, Annotation};
+
 #[derive(Copy, Clone)]
 enum GraphemeWidth {
     Half,
     Full,
+    Tab, // extends to the next tab stop
 }
 
 type GraphemeIdx = usize;
 type ByteIdx = usize;
 impl GraphemeWidth {
-    const fn saturating_add(self, other: usize) -> usize {
+    // The number of columns the grapheme takes up when it starts at the given column of the line.
+    fn at_col(self, col: usize, tab_width: usize) -> usize {
         match self {
-            Self::Half => other.saturating_add(1),
-            Self::Full => other.saturating_add(2),
+            Self::Half => 1,
+            Self::Full => 2,
+            Self::Tab => max(
+                tab_width.saturating_sub(col.checked_rem(tab_width).unwrap_or(0)),
+                1,
+            ),
         }
     }
 }
@@ -50,8 +60,10 @@ impl Line {
         line_str
             .grapheme_indices(true)
             .map(|(byte_idx, grapheme)| {
-                let (replacement, rendered_width) = Self::get_replacement_character(grapheme)
-                    .map_or_else(
+                let (replacement, rendered_width) = if grapheme == "\t" {
+                    (Some(' '), GraphemeWidth::Tab)
+                } else {
+                    Self::get_replacement_character(grapheme).map_or_else(
                         || {
                             let unicode_width = grapheme.width();
                             let rendered_width = match unicode_width {
@@ -61,7 +73,8 @@ impl Line {
                             (None, rendered_width)
                         },
                         |replacement| (Some(replacement), GraphemeWidth::Half),
-                    );
+                    )
+                };
 
                 TextFragment {
                     grapheme: grapheme.to_string(),
@@ -81,7 +94,6 @@ impl Line {
         let width = for_str.width();
         match for_str {
             " " => None,
-            "\t" => Some(' '),
             _ if width > 0 && for_str.trim().is_empty() => Some('␣'),
             _ if width == 0 => {
                 let mut chars = for_str.chars();
@@ -96,25 +108,47 @@ impl Line {
         }
     }
 
-    pub fn get_visible_graphemes(&self, range: Range<GraphemeIdx>) -> String {
+    pub fn get_visible_graphemes(&self, range: Range<GraphemeIdx>, tab_width: usize) -> String {
+        self.get_annotated_visible_substr(range, &[], tab_width).to_string()
+    }
+
+    // Gets the visible part of the line, with each grapheme carrying the type of the last annotation covering it.
+    // The range is given in columns, with tabs extending to the next multiple of the tab width.
+    pub fn get_annotated_visible_substr(
+        &self,
+        range: Range<GraphemeIdx>,
+        annotations: &[Annotation],
+        tab_width: usize,
+    ) -> AnnotatedString {
+        let mut result = AnnotatedString::default();
         if range.start >= range.end {
-            return String::new();
+            return result;
         }
-        let mut result = String::new();
-        let mut current_pos = 0;
+        let mut current_pos: usize = 0;
         for fragment in &self.fragments {
-            let fragment_end = fragment.rendered_width.saturating_add(current_pos);
+            let fragment_end = current_pos
+                .saturating_add(fragment.rendered_width.at_col(current_pos, tab_width));
             if current_pos >= range.end {
                 break;
             }
             if fragment_end > range.start {
-                if fragment_end > range.end || current_pos < range.start {
+                let annotation_type = annotations
+                    .iter()
+                    .rev()
+                    .find(|annotation| annotation.contains(fragment.start_byte_idx))
+                    .map(|annotation| annotation.kind);
+                if matches!(fragment.rendered_width, GraphemeWidth::Tab) {
+                    // Tabs are made of spaces, so they can be clipped to the visible columns
+                    let visible_width = min(fragment_end, range.end)
+                        .saturating_sub(max(current_pos, range.start));
+                    result.push_str(&" ".repeat(visible_width), annotation_type);
+                } else if fragment_end > range.end || current_pos < range.start {
                     // Clip on the right or left
-                    result.push('⋯');
+                    result.push('⋯', annotation_type);
                 } else if let Some(char) = fragment.replacement {
-                    result.push(char);
+                    result.push(char, annotation_type);
                 } else {
-                    result.push_str(&fragment.grapheme);
+                    result.push_str(&fragment.grapheme, annotation_type);
                 }
             }
             current_pos = fragment_end;
@@ -122,34 +156,75 @@ impl Line {
         result
     }
 
+    // Splits the line into rows of at most the given width for soft wrapping, and returns the grapheme index each row starts at.
+    // Rows preferably end after whitespace, and wide graphemes are never split.
+    // A grapheme wider than a row gets a row of its own, which shows it clipped.
+    // If the last row is full, an empty row follows so the caret at the end of the line stays visible.
+    // Tabs keep the width they have in the unwrapped line.
+    pub fn wrap(&self, width: usize, tab_width: usize) -> Vec<GraphemeIdx> {
+        let mut row_starts = vec![0];
+        let mut row_start = 0;
+        let mut row_start_col = 0;
+        let mut col = 0; // the column of the current grapheme within the line
+        let mut word_start = None; // the start of the last word in the row and its column, to wrap at
+        for (idx, fragment) in self.fragments.iter().enumerate() {
+            let fragment_width = fragment.rendered_width.at_col(col, tab_width);
+            while fragment_width.saturating_add(col.saturating_sub(row_start_col)) > width
+                && idx > row_start
+            {
+                (row_start, row_start_col) = word_start
+                    .filter(|&(start, _)| start > row_start)
+                    .unwrap_or((idx, col));
+                row_starts.push(row_start);
+                word_start = None;
+            }
+            col = col.saturating_add(fragment_width);
+            if fragment.grapheme.chars().all(char::is_whitespace) {
+                word_start = Some((idx.saturating_add(1), col));
+            }
+        }
+        if width > 0 && col.saturating_sub(row_start_col) >= width {
+            row_starts.push(self.grapheme_count());
+        }
+        row_starts
+    }
+    // Finds the grapheme at the given width, counted from the grapheme at `from`, without going beyond `until`.
+    pub fn grapheme_idx_at_width(
+        &self,
+        from: GraphemeIdx,
+        until: GraphemeIdx,
+        width: usize,
+        tab_width: usize,
+    ) -> GraphemeIdx {
+        let start_col = self.width_until(from, tab_width);
+        let mut current_width = 0;
+        let mut idx = from;
+        for fragment in self.fragments.iter().take(until).skip(from) {
+            let col = start_col.saturating_add(current_width);
+            current_width =
+                current_width.saturating_add(fragment.rendered_width.at_col(col, tab_width));
+            if current_width > width {
+                break;
+            }
+            idx = idx.saturating_add(1);
+        }
+        idx
+    }
+
     pub fn grapheme_count(&self) -> GraphemeIdx {
         self.fragments.len()
     }
-    pub fn width_until(&self, grapheme_idx: GraphemeIdx) -> GraphemeIdx {
+    // The column the given grapheme starts at.
+    pub fn width_until(&self, grapheme_idx: GraphemeIdx, tab_width: usize) -> GraphemeIdx {
         self.fragments
             .iter()
             .take(grapheme_idx)
-            .map(|fragment| match fragment.rendered_width {
-                GraphemeWidth::Half => 1,
-                GraphemeWidth::Full => 2,
+            .fold(0, |col, fragment| {
+                col.saturating_add(fragment.rendered_width.at_col(col, tab_width))
             })
-            .sum()
-    }
-    pub fn width(&self) -> GraphemeIdx {
-        self.width_until(self.grapheme_count())
-    }
-    // Inserts a character into the line, or appends it at the end if at == grapheme_count + 1
-    pub fn insert_char(&mut self, character: char, at: GraphemeIdx) {
-        debug_assert!(at.saturating_sub(1) <= self.grapheme_count());
-        if let Some(fragment) = self.fragments.get(at) {
-            self.string.insert(fragment.start_byte_idx, character);
-        } else {
-            self.string.push(character);
-        }
-        self.rebuild_fragments();
     }
-    pub fn append_char(&mut self, character: char) {
-        self.insert_char(character, self.grapheme_count());
+    pub fn width(&self, tab_width: usize) -> GraphemeIdx {
+        self.width_until(self.grapheme_count(), tab_width)
     }
     pub fn delete(&mut self, at: GraphemeIdx) {
         debug_assert!(at <= self.grapheme_count());
@@ -163,8 +238,20 @@ impl Line {
         }
     }
 
-    pub fn delete_last(&mut self) {
-        self.delete(self.grapheme_count().saturating_sub(1));
+    pub fn delete_range(&mut self, range: Range<GraphemeIdx>) {
+        let start = self.grapheme_idx_to_byte_idx(range.start);
+        let end = self.grapheme_idx_to_byte_idx(range.end);
+        if start < end {
+            self.string.drain(start..end);
+            self.rebuild_fragments();
+        }
+    }
+
+    pub fn insert_str(&mut self, text: &str, at: GraphemeIdx) {
+        debug_assert!(at <= self.grapheme_count());
+        let byte_idx = self.grapheme_idx_to_byte_idx(at);
+        self.string.insert_str(byte_idx, text);
+        self.rebuild_fragments();
     }
 
     pub fn append(&mut self, other: &Self) {
@@ -181,78 +268,105 @@ impl Line {
             Self::default()
         }
     }
-    fn byte_idx_to_grapheme_idx(&self, byte_idx: ByteIdx) -> GraphemeIdx {
-        debug_assert!(byte_idx <= self.string.len());
-        self.fragments
-            .iter()
-            .position(|fragment| fragment.start_byte_idx >= byte_idx)
+    // The byte ranges of the words in the line, following the Unicode word boundaries.
+    fn word_ranges(&self) -> impl Iterator<Item = Range<ByteIdx>> + '_ {
+        self.string
+            .split_word_bound_indices()
+            .filter(|(_, word)| word.chars().any(char::is_alphanumeric))
+            .map(|(start, word)| start..start.saturating_add(word.len()))
+    }
+    // Finds the start of the word before the given grapheme, or the start of the line if there is none.
+    pub fn previous_word_start(&self, from_grapheme_idx: GraphemeIdx) -> GraphemeIdx {
+        let from_byte_idx = self.grapheme_idx_to_byte_idx(from_grapheme_idx);
+        self.word_ranges()
+            .take_while(|range| range.start < from_byte_idx)
+            .last()
+            .map_or(0, |range| self.byte_idx_to_grapheme_idx(range.start))
+    }
+    // Finds the end of the word after the given grapheme, or the end of the line if there is none.
+    pub fn next_word_end(&self, from_grapheme_idx: GraphemeIdx) -> GraphemeIdx {
+        let from_byte_idx = self.grapheme_idx_to_byte_idx(from_grapheme_idx);
+        self.word_ranges()
+            .find(|range| range.end > from_byte_idx)
             .map_or_else(
-                || {
-                    #[cfg(debug_assertions)]
-                    {
-                        panic!("Fragment not found for byte index: {byte_idx:?}");
-                    }
-                    #[cfg(not(debug_assertions))]
-                    {
-                        0
-                    }
-                },
-                |grapheme_idx| grapheme_idx,
+                || self.grapheme_count(),
+                |range| self.byte_idx_to_grapheme_idx(range.end),
             )
     }
-    fn grapheme_idx_to_byte_idx(&self, grapheme_idx: GraphemeIdx) -> ByteIdx {
+    pub fn byte_idx_to_grapheme_idx(&self, byte_idx: ByteIdx) -> GraphemeIdx {
+        debug_assert!(byte_idx <= self.string.len());
+        if byte_idx >= self.string.len() {
+            return self.grapheme_count();
+        }
+        let grapheme_idx = self
+            .fragments
+            .iter()
+            .position(|fragment| fragment.start_byte_idx >= byte_idx);
+        debug_assert!(
+            grapheme_idx.is_some(),
+            "Fragment not found for byte index: {byte_idx:?}"
+        );
+        grapheme_idx.unwrap_or(0)
+    }
+    pub fn grapheme_idx_to_byte_idx(&self, grapheme_idx: GraphemeIdx) -> ByteIdx {
         debug_assert!(grapheme_idx <= self.grapheme_count());
-        if grapheme_idx == 0 || self.grapheme_count() == 0 {
-            return 0;
+        if grapheme_idx >= self.grapheme_count() {
+            return self.string.len();
         }
-        self.fragments.get(grapheme_idx).map_or_else(
-            || {
-                #[cfg(debug_assertions)]
-                {
-                    panic!("Fragment not found for grapheme index: {grapheme_idx:?}");
-                }
-                #[cfg(not(debug_assertions))]
-                {
-                    0
-                }
-            },
-            |fragment| fragment.start_byte_idx,
-        )
+        self.fragments
+            .get(grapheme_idx)
+            .map_or(0, |fragment| fragment.start_byte_idx)
     }
+    // Finds the first match of the regex starting at or after the given grapheme.
+    // Matches are searched in the whole line, so anchors and word boundaries work as expected.
     pub fn search_forward(
         &self,
-        query: &str,
+        regex: &Regex,
         from_grapheme_idx: GraphemeIdx,
-    ) -> Option<GraphemeIdx> {
-        debug_assert!(from_grapheme_idx <= self.grapheme_count());
-        if from_grapheme_idx == self.grapheme_count() {
+    ) -> Option<Range<GraphemeIdx>> {
+        if from_grapheme_idx > self.grapheme_count() {
             return None;
         }
         let start_byte_idx = self.grapheme_idx_to_byte_idx(from_grapheme_idx);
-        self.string
-            .get(start_byte_idx..)
-            .and_then(|substr| substr.find(query))
-            .map(|byte_idx| self.byte_idx_to_grapheme_idx(byte_idx.saturating_add(start_byte_idx)))
+        regex
+            .find_at(&self.string, start_byte_idx)
+            .map(|found| self.byte_range_to_grapheme_range(found.range()))
     }
+    // Finds the last match of the regex starting before the given grapheme.
     pub fn search_backward(
         &self,
-        query: &str,
+        regex: &Regex,
         from_grapheme_idx: GraphemeIdx,
-    ) -> Option<GraphemeIdx> {
+    ) -> Option<Range<GraphemeIdx>> {
         debug_assert!(from_grapheme_idx <= self.grapheme_count());
 
         if from_grapheme_idx == 0 {
             return None;
         }
-        let end_byte_index = if from_grapheme_idx == self.grapheme_count() {
-            self.string.len()
-        } else {
-            self.grapheme_idx_to_byte_idx(from_grapheme_idx)
-        };
-        self.string
-            .get(..end_byte_index)
-            .and_then(|substr| substr.match_indices(query).last())
-            .map(|(index, _)| self.byte_idx_to_grapheme_idx(index))
+        let end_byte_index = self.grapheme_idx_to_byte_idx(from_grapheme_idx);
+        regex
+            .find_iter(&self.string)
+            .take_while(|found| found.start() < end_byte_index)
+            .last()
+            .map(|found| self.byte_range_to_grapheme_range(found.range()))
+    }
+    fn byte_range_to_grapheme_range(&self, range: Range<ByteIdx>) -> Range<GraphemeIdx> {
+        self.byte_idx_to_grapheme_idx(range.start)..self.byte_idx_to_grapheme_idx(range.end)
+    }
+    // Builds the replacement for the match of the regex starting at the given grapheme,
+    // with references to capture groups like `$1` expanded.
+    pub fn expand_replacement(
+        &self,
+        regex: &Regex,
+        at_grapheme_idx: GraphemeIdx,
+        replacement: &str,
+    ) -> String {
+        let start_byte_idx = self.grapheme_idx_to_byte_idx(at_grapheme_idx);
+        let mut expanded = String::new();
+        if let Some(captures) = regex.captures_at(&self.string, start_byte_idx) {
+            captures.expand(replacement, &mut expanded);
+        }
+        expanded
     }
 }
 
@@ -268,4 +382,58 @@ impl Deref for Line {
     fn deref(&self) -> &Self::Target {
         &self.string
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(text: &str, width: usize) -> Vec<String> {
+        let line = Line::from(text);
+        let starts = line.wrap(width, 4);
+        starts
+            .iter()
+            .zip(starts.iter().skip(1).chain([&line.grapheme_count()]))
+            .map(|(&start, &end)| {
+                line.get(line.grapheme_idx_to_byte_idx(start)..line.grapheme_idx_to_byte_idx(end))
+                    .unwrap_or_default()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wraps_after_whitespace() {
+        assert_eq!(rows("the quick brown fox", 10), ["the quick ", "brown fox"]);
+        assert_eq!(rows("short", 10), ["short"]);
+        assert_eq!(rows("", 10), [""]);
+    }
+
+    #[test]
+    fn splits_words_longer_than_a_row() {
+        assert_eq!(rows("abcdefghij kl", 4), ["abcd", "efgh", "ij ", "kl"]);
+    }
+
+    #[test]
+    fn adds_an_empty_row_behind_a_full_one() {
+        assert_eq!(rows("abcd", 4), ["abcd", ""]);
+        assert_eq!(rows("ab cd", 3), ["ab ", "cd"]);
+    }
+
+    #[test]
+    fn never_splits_wide_graphemes() {
+        assert_eq!(rows("a日本語", 4), ["a日", "本語", ""]);
+        // A grapheme wider than the row still gets a row of its own
+        assert_eq!(rows("日本", 1), ["日", "本", ""]);
+    }
+
+    #[test]
+    fn keeps_the_width_of_tabs() {
+        assert_eq!(rows("a\tbc d", 6), ["a\t", "bc d"]);
+    }
+
+    #[test]
+    fn wraps_into_many_rows() {
+        let line = Line::from("word ".repeat(20_000).as_str());
+        assert_eq!(line.wrap(10, 4).len(), 10_001);
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{create_dir_all, read_to_string, File},
+    io::{Error, ErrorKind, Write},
+    path::PathBuf,
+};
+
+const HISTORY_PATH_VAR: &str = "QUIK_HISTORY";
+const MAX_ENTRIES: usize = 100; // per kind of prompt
+
+/// The values entered into the prompts, kept between sessions in `$QUIK_HISTORY`,
+/// `$XDG_DATA_HOME/quik/history` or `~/.local/share/quik/history`, in this order.
+/// Each line of the file holds one entry, prefixed with the kind of prompt it was entered into and a tab.
+#[derive(Default)]
+pub struct PromptHistory {
+    entries: BTreeMap<String, Vec<String>>, // oldest first, sorted by kind to keep the file stable
+    path: Option<PathBuf>,
+}
+
+impl PromptHistory {
+    // Loads the history file. A missing file is not an error and yields an empty history.
+    pub fn load() -> Result<Self, Error> {
+        let mut history = Self {
+            path: Self::path(),
+            ..Self::default()
+        };
+        let Some(path) = &history.path else {
+            return Ok(history);
+        };
+        let contents = match read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(history),
+            Err(err) => return Err(err),
+        };
+        for line in contents.lines() {
+            if let Some((kind, value)) = line.split_once('\t') {
+                history.push(kind, value);
+            }
+        }
+        Ok(history)
+    }
+
+    fn path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os(HISTORY_PATH_VAR) {
+            return Some(PathBuf::from(path));
+        }
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+            })
+            .map(|dir| dir.join("quik").join("history"))
+    }
+
+    pub fn entries(&self, kind: &str) -> &[String] {
+        self.entries.get(kind).map_or(&[], Vec::as_slice)
+    }
+
+    // Adds a value to the history of the given kind of prompt and writes the history file.
+    // Entering a value again moves it to the end instead of keeping it twice.
+    pub fn add(&mut self, kind: &str, value: &str) -> Result<(), Error> {
+        if value.is_empty() {
+            return Ok(());
+        }
+        self.push(kind, value);
+        self.save()
+    }
+
+    fn push(&mut self, kind: &str, value: &str) {
+        let entries = self.entries.entry(kind.to_string()).or_default();
+        entries.retain(|entry| entry != value);
+        entries.push(value.to_string());
+        if entries.len() > MAX_ENTRIES {
+            entries.remove(0);
+        }
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        let mut file = File::create(path)?;
+        for (kind, entries) in &self.entries {
+            for entry in entries {
+                writeln!(file, "{kind}\t{entry}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process};
+
+    #[test]
+    fn saves_kinds_in_a_stable_order() {
+        let dir = env::temp_dir().join(format!("quik-history-{}", process::id()));
+        let path = dir.join("history");
+        let mut history = PromptHistory {
+            path: Some(path.clone()),
+            ..PromptHistory::default()
+        };
+        for (kind, value) in [
+            ("search", "foo"),
+            ("save", "a.rs"),
+            ("go_to", "12"),
+            ("search", "bar"),
+        ] {
+            history.add(kind, value).unwrap();
+        }
+        history.add("search", "foo").unwrap();
+        history.add("search", "").unwrap();
+        assert_eq!(
+            read_to_string(&path).unwrap(),
+            "go_to\t12\nsave\ta.rs\nsearch\tbar\nsearch\tfoo\n"
+        );
+        assert_eq!(history.entries("search"), ["bar", "foo"]);
+        assert!(history.entries("replace").is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_latest_entries() {
+        let mut history = PromptHistory::default();
+        for idx in 0..=MAX_ENTRIES {
+            history.add("search", &idx.to_string()).unwrap();
+        }
+        let entries = history.entries("search");
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries.first().map(String::as_str), Some("1"));
+    }
+}
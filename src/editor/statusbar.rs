@@ -1,6 +1,6 @@
 use std::io::Error;
 
-use super::{DocumentStatus, Size, Terminal, UIComponent};
+use super::{DocumentStatus, Position, Size, Terminal, UIComponent};
 
 #[derive(Default)]
 pub struct StatusBar {
@@ -30,7 +30,7 @@ impl UIComponent for StatusBar {
     fn set_size(&mut self, size: Size) {
         self.size = size;
     }
-    fn draw(&mut self, origin_row: usize) -> Result<(), Error> {
+    fn draw(&mut self, origin: Position) -> Result<(), Error> {
         //Assemble the first part of the status bar
         let line_count = self.current_status.line_count_to_string();
         let modified_indicator = self.current_status.modified_indicator_to_string();
@@ -41,7 +41,11 @@ impl UIComponent for StatusBar {
         );
 
         // Assemble the whole status bar, with the position indicator at the back
-        let position_indicator = self.current_status.position_indicator_to_string();
+        let position_indicator = format!(
+            "{} | {}",
+            self.current_status.file_type_to_string(),
+            self.current_status.position_indicator_to_string()
+        );
         let remainder_len = self.size.width.saturating_sub(beginning.len());
         let status = format!("{beginning}{position_indicator:>remainder_len$}");
 
@@ -51,7 +55,7 @@ impl UIComponent for StatusBar {
         } else {
             String::new()
         };
-        Terminal::print_inverted_row(origin_row, &to_print)?;
+        Terminal::print_inverted_row(origin.row, &to_print)?;
 
         Ok(())
     }
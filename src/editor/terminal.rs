@@ -1,5 +1,5 @@
 use crossterm::cursor::{Hide, MoveTo, Show};
-use crossterm::style::{Attribute, Print};
+use crossterm::style::{Attribute, Print, PrintStyledContent};
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, size, Clear, ClearType, DisableLineWrap, EnableLineWrap,
     EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
@@ -7,7 +7,11 @@ use crossterm::terminal::{
 use crossterm::{queue, Command};
 use std::io::{stdout, Error, Write};
 
-use super::{Position, Size};
+use super::{AnnotatedString, AnnotationType, Position, Size};
+mod attribute;
+use attribute::style_for;
+mod osc52;
+use osc52::CopyToClipboard;
 
 /// Represents the Terminal.
 /// Edge Case for platforms where `usize` < `u16`:
@@ -79,6 +83,10 @@ impl Terminal {
         Self::queue_command(SetTitle(title))?;
         Ok(())
     }
+    pub fn copy_to_clipboard(text: &str) -> Result<(), Error> {
+        Self::queue_command(CopyToClipboard(text))?;
+        Ok(())
+    }
     pub fn print(string: &str) -> Result<(), Error> {
         Self::queue_command(Print(string))?;
         Ok(())
@@ -89,6 +97,27 @@ impl Terminal {
         Self::print(line_text)?;
         Ok(())
     }
+    pub fn print_at(position: Position, text: &str) -> Result<(), Error> {
+        Self::move_caret_to(position)?;
+        Self::print(text)?;
+        Ok(())
+    }
+    pub fn print_annotated_at(
+        position: Position,
+        annotated_string: &AnnotatedString,
+    ) -> Result<(), Error> {
+        Self::move_caret_to(position)?;
+        for part in annotated_string.parts() {
+            if let Some(annotation_type) = part.annotation_type {
+                Self::queue_command(PrintStyledContent(
+                    style_for(annotation_type).apply(part.string),
+                ))?;
+            } else {
+                Self::print(part.string)?;
+            }
+        }
+        Ok(())
+    }
     pub fn print_inverted_row(row: usize, line_text: &str) -> Result<(), Error> {
         let width = Self::size()?.width;
         Self::print_row(
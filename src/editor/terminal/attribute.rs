@@ -0,0 +1,34 @@
+use crossterm::style::{Attribute, Color, ContentStyle};
+
+use super::AnnotationType;
+
+// Determines how an annotated part of the text is styled on screen.
+pub fn style_for(annotation_type: AnnotationType) -> ContentStyle {
+    let mut style = ContentStyle::new();
+    match annotation_type {
+        AnnotationType::Selection => style.attributes.set(Attribute::Reverse),
+        AnnotationType::Keyword => style.foreground_color = Some(Color::Magenta),
+        AnnotationType::Type => style.foreground_color = Some(Color::Cyan),
+        AnnotationType::Number => style.foreground_color = Some(Color::Yellow),
+        AnnotationType::String => style.foreground_color = Some(Color::Green),
+        AnnotationType::Comment | AnnotationType::LineNumber => {
+            style.foreground_color = Some(Color::DarkGrey);
+        }
+        AnnotationType::Heading => {
+            style.foreground_color = Some(Color::Blue);
+            style.attributes.set(Attribute::Bold);
+        }
+        AnnotationType::Emphasis => style.attributes.set(Attribute::Italic),
+        AnnotationType::Code => style.foreground_color = Some(Color::DarkGreen),
+        AnnotationType::SearchMatch => {
+            style.foreground_color = Some(Color::Black);
+            style.background_color = Some(Color::DarkYellow);
+        }
+        AnnotationType::CurrentSearchMatch => {
+            style.foreground_color = Some(Color::Black);
+            style.background_color = Some(Color::Yellow);
+            style.attributes.set(Attribute::Bold);
+        }
+    }
+    style
+}
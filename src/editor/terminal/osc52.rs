@@ -0,0 +1,82 @@
+use std::fmt;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Places text into the clipboard of the host terminal using the OSC 52 escape sequence.
+/// Since the sequence travels with the regular output, this also works over SSH,
+/// provided the terminal emulator supports (and allows) it.
+pub struct CopyToClipboard<'a>(pub &'a str);
+
+impl crossterm::Command for CopyToClipboard<'_> {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        write!(f, "\x1b]52;c;{}\x07", encode_base64(self.0.as_bytes()))
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> std::io::Result<()> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "OSC 52 is only supported through ANSI escape sequences",
+        ))
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len().div_ceil(3).saturating_mul(4));
+    for chunk in bytes.chunks(3) {
+        let first = chunk.first().copied().unwrap_or_default();
+        let second = chunk.get(1).copied().unwrap_or_default();
+        let third = chunk.get(2).copied().unwrap_or_default();
+        let sextets = [
+            first >> 2,
+            ((first & 0b11) << 4) | (second >> 4),
+            ((second & 0b1111) << 2) | (third >> 6),
+            third & 0b11_1111,
+        ];
+        // A chunk of n bytes yields n + 1 characters, the rest is padding.
+        for (idx, sextet) in sextets.iter().enumerate() {
+            if idx <= chunk.len() {
+                result.push(char::from(
+                    BASE64_ALPHABET
+                        .get(usize::from(*sextet))
+                        .copied()
+                        .unwrap_or(b'='),
+                ));
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_rfc_4648_vectors() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foo"), "Zm9v");
+        assert_eq!(encode_base64(b"foob"), "Zm9vYg==");
+        assert_eq!(encode_base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn encodes_multibyte_utf8() {
+        assert_eq!(encode_base64("ü".as_bytes()), "w7w=");
+        assert_eq!(encode_base64("日本".as_bytes()), "5pel5pys");
+        assert_eq!(encode_base64("🦀".as_bytes()), "8J+mgA==");
+    }
+
+    #[test]
+    fn wraps_text_in_osc_52() {
+        let mut output = String::new();
+        crossterm::Command::write_ansi(&CopyToClipboard("foo"), &mut output).unwrap();
+        assert_eq!(output, "\x1b]52;c;Zm9v\x07");
+    }
+}
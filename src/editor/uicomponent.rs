@@ -1,6 +1,6 @@
 use std::io::Error;
 
-use super::Size;
+use super::{Position, Size};
 
 pub trait UIComponent {
     // Marks this UI component as in need of redrawing (or not)
@@ -17,9 +17,9 @@ pub trait UIComponent {
     fn set_size(&mut self, size: Size);
 
     // Draw this component if it's visible and in need of redrawing
-    fn render(&mut self, origin_row: usize) {
+    fn render(&mut self, origin: Position) {
         if self.needs_redraw() {
-            if let Err(err) = self.draw(origin_row) {
+            if let Err(err) = self.draw(origin) {
                 #[cfg(debug_assertions)]
                 {
                     panic!("Could not render component: {err:?}");
@@ -34,5 +34,5 @@ pub trait UIComponent {
         }
     }
     // Method to actually draw the component, must be implemented by each component
-    fn draw(&mut self, origin_row: usize) -> Result<(), Error>;
+    fn draw(&mut self, origin: Position) -> Result<(), Error>;
 }
\ No newline at end of file
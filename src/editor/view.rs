@@ -1,17 +1,39 @@
-use std::{cmp::min, io::Error};
+use regex::Regex;
+use std::{
+    cell::RefCell,
+    cmp::{max, min},
+    collections::HashMap,
+    fmt,
+    io::Error,
+    iter,
+    ops::Range,
+    rc::Rc,
+};
 
 use super::{
     command::{Edit, Move},
-    Col, DocumentStatus, Line, Position, Row, Size, Terminal, UIComponent, NAME, VERSION,
+    AnnotatedString, Annotation, AnnotationType, ClipboardContent, Col, Config, DocumentStatus,
+    FileType, FileTypeSettings, Line, Position, Row, Size, Terminal, UIComponent, NAME, VERSION,
 };
 mod buffer;
-use buffer::Buffer;
+pub use buffer::Buffer;
+mod changelog;
 mod location;
 use location::Location;
 mod fileinfo;
 use fileinfo::FileInfo;
+mod gototarget;
+pub use gototarget::GoToTarget;
+mod replaceinfo;
+use replaceinfo::ReplaceInfo;
 mod searchinfo;
 use searchinfo::SearchInfo;
+pub use searchinfo::{MatchCount, SearchOptions};
+mod history;
+use history::Snapshot;
+mod visualrow;
+use visualrow::VisualRow;
+mod highlighter;
 
 #[derive(Default, Eq, PartialEq, Clone, Copy)]
 pub enum SearchDirection {
@@ -20,35 +42,172 @@ pub enum SearchDirection {
     Backward,
 }
 
+#[derive(Default, Eq, PartialEq, Clone, Copy)]
+pub enum LineNumbers {
+    #[default]
+    Off,
+    Absolute,
+    Relative, // the distance from the caret line, which itself shows its absolute number
+}
+
+impl LineNumbers {
+    const fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Absolute,
+            Self::Absolute => Self::Relative,
+            Self::Relative => Self::Off,
+        }
+    }
+}
+
+impl fmt::Display for LineNumbers {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Off => "off",
+            Self::Absolute => "absolute",
+            Self::Relative => "relative",
+        };
+        write!(formatter, "{name}")
+    }
+}
+
+// Buffers are shared between all views showing them, so edits in one view appear in the others.
+pub type SharedBuffer = Rc<RefCell<Buffer>>;
+
 #[derive(Default)]
 pub struct View {
-    buffer: Buffer,
+    buffer: SharedBuffer,
+    buffer_revision: usize, // the revision of the buffer when this view was last drawn
+    synced_revision: usize, // the buffer revision the caret and selection have been moved along to
     needs_redraw: bool,
     size: Size,
     text_location: Location,
+    selection_anchor: Option<Location>, // the other end of the selection, the caret being at text_location
+    preferred_col: Option<(Location, usize)>, // the visual column vertical moves keep, and where the caret was moved to last
     scroll_offset: Position,
     search_info: Option<SearchInfo>,
+    replace_info: Option<ReplaceInfo>,
+    line_numbers: LineNumbers,
+    drawn_caret_line: usize, // the caret line when the view was last drawn, which relative line numbers depend on
+    soft_wrap: bool,
+    wrap_top_row: usize, // in soft wrap mode, the row of the top line (scroll_offset.row) shown first
+    row_starts: RefCell<RowStarts>,
+}
+
+// The rows the lines have been wrapped into in soft wrap mode, kept until the text or the width changes.
+#[derive(Default)]
+struct RowStarts {
+    wrapped_for: (usize, usize, usize), // the buffer revision, the text width and the tab width
+    lines: HashMap<usize, Rc<[usize]>>,
 }
 
 impl View {
+    pub fn new(buffer: SharedBuffer) -> Self {
+        let mut view = Self::default();
+        view.set_buffer(buffer);
+        view
+    }
+    // Creates another view on the same buffer, starting out at the same position.
+    pub fn duplicate(&self) -> Self {
+        Self {
+            buffer: Rc::clone(&self.buffer),
+            needs_redraw: true,
+            synced_revision: self.synced_revision,
+            text_location: self.text_location,
+            scroll_offset: self.scroll_offset,
+            line_numbers: self.line_numbers,
+            soft_wrap: self.soft_wrap,
+            wrap_top_row: self.wrap_top_row,
+            ..Self::default()
+        }
+    }
+    pub const fn size(&self) -> Size {
+        self.size
+    }
+    pub const fn buffer(&self) -> &SharedBuffer {
+        &self.buffer
+    }
+    // Shows another buffer in this view. The position in the previous buffer is remembered there,
+    // so it can be restored once the buffer is shown again.
+    pub fn set_buffer(&mut self, buffer: SharedBuffer) {
+        self.buffer.borrow_mut().last_snapshot = self.snapshot();
+        let snapshot = buffer.borrow().last_snapshot;
+        self.synced_revision = buffer.borrow().revision;
+        self.buffer = buffer;
+        self.text_location = snapshot.location;
+        self.scroll_offset = snapshot.scroll_offset;
+        self.selection_anchor = None;
+        self.search_info = None;
+        self.row_starts.take(); // revisions of different buffers can't be told apart
+        self.clamp_to_buffer();
+        self.scroll_text_location_into_view();
+        self.set_needs_redraw(true);
+    }
+
+    // Switches to the next line number mode and returns it.
+    pub fn cycle_line_numbers(&mut self) -> LineNumbers {
+        self.line_numbers = self.line_numbers.next();
+        self.scroll_text_location_into_view();
+        self.set_needs_redraw(true);
+        self.line_numbers
+    }
+    // Switches soft wrapping on or off and returns whether it is on.
+    pub fn toggle_soft_wrap(&mut self) -> bool {
+        self.soft_wrap = !self.soft_wrap;
+        self.scroll_offset.col = 0;
+        self.wrap_top_row = 0;
+        self.scroll_text_location_into_view();
+        self.set_needs_redraw(true);
+        self.soft_wrap
+    }
+    // The width of the line number gutter: enough for the digits of the highest line number, plus a blank.
+    fn gutter_width(&self) -> usize {
+        if self.line_numbers == LineNumbers::Off {
+            return 0;
+        }
+        let height = self.buffer.borrow().height().max(1);
+        let width = height.ilog10().saturating_add(2).try_into().unwrap_or(0);
+        // Panes too narrow to show any text next to the gutter leave it out
+        if width < self.size.width {
+            width
+        } else {
+            0
+        }
+    }
+    // The width available for the text, next to the gutter.
+    fn text_width(&self) -> usize {
+        self.size.width.saturating_sub(self.gutter_width())
+    }
+
     pub fn get_status(&self) -> DocumentStatus {
+        let buffer = self.buffer.borrow();
         DocumentStatus {
-            total_lines: self.buffer.height(),
+            total_lines: buffer.height(),
             current_line_idx: self.text_location.line_idx,
-            file_name: format!("{}", self.buffer.file_info),
-            is_modified: self.buffer.dirty,
+            file_name: format!("{}", buffer.file_info),
+            is_modified: buffer.dirty,
+            is_readonly: buffer.readonly,
+            file_type: buffer.file_info.get_file_type(),
+            settings: buffer.settings.clone(),
         }
     }
 
-    pub const fn is_file_loaded(&self) -> bool {
-        self.buffer.is_file_loaded()
+    pub fn is_readonly(&self) -> bool {
+        self.buffer.borrow().readonly
+    }
+    pub fn is_file_loaded(&self) -> bool {
+        self.buffer.borrow().is_file_loaded()
     }
 
     pub fn enter_search(&mut self) {
+        self.clear_selection();
         self.search_info = Some(SearchInfo {
             prev_location: self.text_location,
             prev_scroll_offset: self.scroll_offset,
             query: None,
+            current: None,
+            wrapped: false,
+            match_starts: None,
         });
     }
     pub fn exit_search(&mut self) {
@@ -63,51 +222,135 @@ impl View {
         self.search_info = None;
     }
 
-    pub fn search(&mut self, query: &str) {
+    // Searches for the query from the caret onwards.
+    // An invalid regex clears the query, so nothing is found until it has been corrected.
+    pub fn search(&mut self, query: &str, options: SearchOptions) {
         if let Some(search_info) = &mut self.search_info {
-            search_info.query = Some(Line::from(query));
+            search_info.query = if query.is_empty() {
+                None
+            } else {
+                options.compile(query).ok()
+            };
+            search_info.match_starts = None;
         }
         self.search_in_direction(self.text_location, SearchDirection::default());
     }
 
-    // Attempts to get the current search query - for scenarios where the search query absolutely must be there.
-    // Panics if not present in debug, or if search info is not present in debug
-    // Returns None on release.
-    fn get_search_query(&self) -> Option<&Line> {
+    // Attempts to get the current search query, which is missing while the query is empty or invalid.
+    // Panics if search info is not present in debug.
+    fn get_search_query(&self) -> Option<&Regex> {
         let query = self
             .search_info
             .as_ref()
             .and_then(|search_info| search_info.query.as_ref());
 
         debug_assert!(
-            query.is_some(),
+            self.search_info.is_some(),
             "Attempting to search with malformed searchinfo present"
         );
         query
     }
 
     fn search_in_direction(&mut self, from: Location, direction: SearchDirection) {
-        if let Some(location) = self.get_search_query().and_then(|query| {
-            if query.is_empty() {
-                None
-            } else if direction == SearchDirection::Forward {
-                self.buffer.search_forward(query, from)
+        let found = self.get_search_query().and_then(|query| {
+            if direction == SearchDirection::Forward {
+                self.buffer.borrow().search_forward(query, from)
             } else {
-                self.buffer.search_backward(query, from)
+                self.buffer.borrow().search_backward(query, from)
             }
-        }) {
-            self.text_location = location;
+        });
+        if let Some(search_info) = &mut self.search_info {
+            search_info.wrapped = found.as_ref().is_some_and(|found| {
+                if direction == SearchDirection::Forward {
+                    found.start < from
+                } else {
+                    found.start >= from
+                }
+            });
+            search_info.current.clone_from(&found);
+        }
+        if let Some(found) = found {
+            self.text_location = found.start;
             self.center_text_location();
+        }
+        // The highlighted matches change with every search
+        self.set_needs_redraw(true);
+    }
+    // Counts the matches of the current search and determines which of them is the current one.
+    // Empty matches are left out, as they are neither highlighted nor navigated to.
+    // The matches are only searched for again once the query or the text has changed.
+    pub fn match_count(&mut self) -> Option<MatchCount> {
+        let revision = self.buffer.borrow().revision;
+        let search_info = self.search_info.as_mut()?;
+        let query = search_info.query.as_ref()?;
+        if search_info
+            .match_starts
+            .as_ref()
+            .is_none_or(|(cached_revision, _)| *cached_revision != revision)
+        {
+            let buffer = self.buffer.borrow();
+            let starts = buffer
+                .lines
+                .iter()
+                .enumerate()
+                .flat_map(|(line_idx, line)| {
+                    query
+                        .find_iter(line)
+                        .filter(|found| !found.is_empty())
+                        .map(move |found| Location {
+                            line_idx,
+                            grapheme_idx: line.byte_idx_to_grapheme_idx(found.start()),
+                        })
+                })
+                .collect();
+            search_info.match_starts = Some((revision, starts));
+        }
+        let (_, starts) = search_info.match_starts.as_ref()?;
+        Some(MatchCount {
+            current: search_info.current.as_ref().map(|current| {
+                starts
+                    .partition_point(|start| *start < current.start)
+                    .saturating_add(1)
+            }),
+            total: starts.len(),
+            wrapped: search_info.wrapped,
+        })
+    }
+    // Marks all matches of the current search within the given line, the current match last,
+    // so it takes precedence.
+    fn search_annotations(&self, line_idx: usize, line: &Line) -> Vec<Annotation> {
+        let Some(search_info) = &self.search_info else {
+            return Vec::new();
+        };
+        let Some(query) = &search_info.query else {
+            return Vec::new();
         };
+        let mut annotations: Vec<Annotation> = query
+            .find_iter(line)
+            .filter(|found| !found.is_empty())
+            .map(|found| Annotation {
+                kind: AnnotationType::SearchMatch,
+                start: found.start(),
+                end: found.end(),
+            })
+            .collect();
+        if let Some(current) = search_info
+            .current
+            .as_ref()
+            .filter(|current| current.start.line_idx == line_idx)
+        {
+            annotations.push(Annotation {
+                kind: AnnotationType::CurrentSearchMatch,
+                start: line.grapheme_idx_to_byte_idx(current.start.grapheme_idx),
+                end: line.grapheme_idx_to_byte_idx(current.end.grapheme_idx),
+            });
+        }
+        annotations
     }
     pub fn search_next(&mut self) {
-        let step_right = self
-            .get_search_query()
-            .map_or(1, |query| min(query.grapheme_count(), 1));
-
         let location = Location {
             line_idx: self.text_location.line_idx,
-            grapheme_idx: self.text_location.grapheme_idx.saturating_add(step_right), //Start the new search behind the current match
+            grapheme_idx: self.text_location.grapheme_idx.saturating_add(1), //Start the new search behind the start of the current match
         };
         self.search_in_direction(location, SearchDirection::Forward);
     }
@@ -115,30 +358,241 @@ impl View {
         self.search_in_direction(self.text_location, SearchDirection::Backward);
     }
 
-    pub fn load(&mut self, file_name: &str) -> Result<(), Error> {
-        let buffer = Buffer::load(file_name)?;
-        self.buffer = buffer;
-        self.set_needs_redraw(true);
+    // Starts replacing matches of the query from the caret onwards, by selecting the first one.
+    // In regex mode, references to capture groups in the replacement are expanded.
+    pub fn start_replace(
+        &mut self,
+        query: &str,
+        replacement: &str,
+        options: SearchOptions,
+    ) -> Result<(), regex::Error> {
+        self.clear_selection();
+        self.replace_info = Some(ReplaceInfo {
+            query: options.compile(query)?,
+            replacement: replacement.to_string(),
+            expand: options.regex,
+            origin: self.text_location,
+            wrapped: false,
+            current: None,
+            count: 0,
+        });
+        self.find_next_replacement(self.text_location);
         Ok(())
     }
+    // Returns true while there is a match waiting to be replaced or skipped.
+    pub fn has_replacement(&self) -> bool {
+        self.replace_info
+            .as_ref()
+            .is_some_and(|replace_info| replace_info.current.is_some())
+    }
+    pub fn replace_current(&mut self) {
+        self.follow_buffer_changes();
+        self.buffer
+            .borrow_mut()
+            .begin_step(self.snapshot(), false);
+        self.replace_and_advance();
+        self.skip_own_changes();
+    }
+    pub fn skip_current(&mut self) {
+        if let Some(current) = self
+            .replace_info
+            .as_ref()
+            .and_then(|replace_info| replace_info.current.clone())
+        {
+            self.find_next_replacement(Self::behind_match(&current, current.end));
+        }
+    }
+    // Replaces the current match and all following ones, as a single undoable step.
+    pub fn replace_all(&mut self) {
+        self.follow_buffer_changes();
+        self.buffer
+            .borrow_mut()
+            .begin_step(self.snapshot(), false);
+        while self.has_replacement() {
+            self.replace_and_advance();
+        }
+        self.skip_own_changes();
+    }
+    // Stops replacing and returns the number of replacements made.
+    pub fn finish_replace(&mut self) -> usize {
+        self.clear_selection();
+        self.replace_info
+            .take()
+            .map_or(0, |replace_info| replace_info.count)
+    }
+    fn replace_and_advance(&mut self) {
+        let Some(replace_info) = &mut self.replace_info else {
+            return;
+        };
+        let Some(current) = replace_info.current.clone() else {
+            return;
+        };
+        let mut buffer = self.buffer.borrow_mut();
+        let replacement = if replace_info.expand {
+            buffer.lines.get(current.start.line_idx).map_or_else(String::new, |line| {
+                line.expand_replacement(
+                    &replace_info.query,
+                    current.start.grapheme_idx,
+                    &replace_info.replacement,
+                )
+            })
+        } else {
+            replace_info.replacement.clone()
+        };
+        buffer.delete_range(current.start, current.end);
+        let end = buffer.insert_str(&replacement, current.start);
+        drop(buffer);
+        // Replacements are made within a line, so only the origin's line can be shifted
+        let origin = &mut replace_info.origin;
+        if origin.line_idx == current.end.line_idx && current.start < *origin {
+            origin.grapheme_idx = origin
+                .grapheme_idx
+                .saturating_sub(current.end.grapheme_idx)
+                .saturating_add(end.grapheme_idx);
+        }
+        replace_info.count = replace_info.count.saturating_add(1);
+        self.find_next_replacement(Self::behind_match(&current, end));
+    }
+    // Where to continue searching after a match. Empty matches are stepped over, so they aren't found again and again.
+    fn behind_match(current: &Range<Location>, end: Location) -> Location {
+        if current.start == current.end {
+            Location {
+                line_idx: end.line_idx,
+                grapheme_idx: end.grapheme_idx.saturating_add(1),
+            }
+        } else {
+            end
+        }
+    }
+    // Selects the next match at or after the given location, unless all matches up to the origin have been visited.
+    fn find_next_replacement(&mut self, from: Location) {
+        let Some(replace_info) = &mut self.replace_info else {
+            return;
+        };
+        let found = self
+            .buffer
+            .borrow()
+            .search_forward(&replace_info.query, from);
+        // The search wraps around at the end of the buffer, and ends once it passes the origin again.
+        replace_info.current = found.filter(|found| {
+            let wraps_now = found.start < from;
+            let is_done = ((replace_info.wrapped || wraps_now) && found.start >= replace_info.origin)
+                || (replace_info.wrapped && wraps_now);
+            replace_info.wrapped |= wraps_now;
+            !is_done
+        });
+        if let Some(current) = replace_info.current.clone() {
+            self.selection_anchor = Some(current.start);
+            self.text_location = current.end;
+            self.center_text_location();
+        }
+        self.set_needs_redraw(true);
+    }
 
     pub fn save(&mut self) -> Result<(), Error> {
-        self.buffer.save()
+        self.buffer.borrow_mut().save()
     }
-    pub fn save_as(&mut self, file_name: &str) -> Result<(), Error> {
-        self.buffer.save_as(file_name)
+    pub fn save_as(&mut self, file_name: &str, config: &Config) -> Result<(), Error> {
+        self.buffer.borrow_mut().save_as(file_name, config)
     }
 
 
     pub fn handle_edit_command(&mut self, command: Edit) {
+        self.follow_buffer_changes();
+        self.edit_text(command);
+        self.skip_own_changes();
+    }
+    fn edit_text(&mut self, command: Edit) {
+        self.preferred_col = None;
+        // Tab on a selection across lines indents all of them, Shift-Tab dedents the selected lines or the caret line
+        let shifts_lines = matches!(command, Edit::Dedent)
+            || (matches!(command, Edit::InsertTab) && self.selects_multiple_lines());
+        if matches!(command, Edit::Undo | Edit::Redo) {
+            self.clear_selection();
+        } else {
+            // Consecutive character inserts are grouped into a single undo step
+            let coalesce = matches!(command, Edit::Insert(_) | Edit::InsertTab) && !shifts_lines;
+            self.buffer
+                .borrow_mut()
+                .begin_step(self.snapshot(), coalesce);
+            // An active selection is replaced by inserts and removed as a whole by deletes
+            if !shifts_lines
+                && self.delete_selection()
+                && matches!(
+                    command,
+                    Edit::Delete
+                        | Edit::DeleteBackward
+                        | Edit::DeleteWord
+                        | Edit::DeleteWordBackward
+                )
+            {
+                return;
+            }
+        }
         match command {
             Edit::Insert(character) => self.insert_char(character),
+            Edit::InsertTab if shifts_lines => self.shift_lines(true),
+            Edit::InsertTab => self.insert_tab(),
+            Edit::Dedent => self.shift_lines(false),
             Edit::Delete => self.delete(),
             Edit::DeleteBackward => self.delete_backward(),
             Edit::InsertNewline => self.insert_newline(),
+            Edit::Undo => self.undo(),
+            Edit::Redo => self.redo(),
+            Edit::DeleteWord => self.delete_word(),
+            Edit::DeleteWordBackward => self.delete_word_backward(),
         }
     }
     pub fn handle_move_command(&mut self, command: Move) {
+        self.clear_selection();
+        self.move_text_location(command);
+    }
+    pub fn handle_select_command(&mut self, command: Move) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.text_location);
+        }
+        self.move_text_location(command);
+        self.set_needs_redraw(true);
+    }
+    // Moves the caret to the given line and column and centres it.
+    // Returns an error describing the valid range if the target is outside of the buffer.
+    pub fn go_to(&mut self, target: GoToTarget) -> Result<(), String> {
+        let buffer = self.buffer.borrow();
+        let last_line = max(buffer.height(), 1);
+        let caret_line = self.text_location.line_idx.saturating_add(1);
+        let (line, col) = match target {
+            GoToTarget::Line { line, col } => (Some(line), col),
+            GoToTarget::Forward(lines) => (caret_line.checked_add(lines), None),
+            GoToTarget::Backward(lines) => (caret_line.checked_sub(lines), None),
+            GoToTarget::Percentage(percentage) => (
+                Some(max(last_line.saturating_mul(percentage).div_ceil(100), 1)),
+                None,
+            ),
+        };
+        let line_idx = line
+            .filter(|line| (1..=last_line).contains(line))
+            .map(|line| line.saturating_sub(1))
+            .ok_or_else(|| format!("{target} is out of range (lines 1-{last_line})"))?;
+        let last_col = buffer
+            .lines
+            .get(line_idx)
+            .map_or(0, Line::grapheme_count)
+            .saturating_add(1);
+        let grapheme_idx = col
+            .map_or(Some(1), |col| (1..=last_col).contains(&col).then_some(col))
+            .map(|col| col.saturating_sub(1))
+            .ok_or_else(|| format!("{target} is out of range (columns 1-{last_col})"))?;
+        drop(buffer);
+
+        self.clear_selection();
+        self.text_location = Location {
+            line_idx,
+            grapheme_idx,
+        };
+        self.center_text_location();
+        Ok(())
+    }
+    fn move_text_location(&mut self, command: Move) {
         let Size { height, .. } = self.size;
         // This match moves the positon, but does not check for all boundaries.
         // The final boundarline checking happens after the match statement.
@@ -151,48 +605,358 @@ impl View {
             Move::PageDown => self.move_down(height.saturating_sub(1)),
             Move::StartOfLine => self.move_to_start_of_line(),
             Move::EndOfLine => self.move_to_end_of_line(),
+            Move::WordLeft => self.move_word_left(),
+            Move::WordRight => self.move_word_right(),
+            Move::StartOfBuffer => self.move_to_start_of_buffer(),
+            Move::EndOfBuffer => self.move_to_end_of_buffer(),
+            Move::PreviousParagraph => self.move_to_previous_paragraph(),
+            Move::NextParagraph => self.move_to_next_paragraph(),
+        }
+        if !matches!(command, Move::Up | Move::Down | Move::PageUp | Move::PageDown) {
+            self.preferred_col = None;
+        }
+        self.scroll_text_location_into_view();
+    }
+
+    // Returns the selected range, ordered from start to end.
+    fn selection(&self) -> Option<(Location, Location)> {
+        self.selection_anchor.map(|anchor| {
+            if anchor <= self.text_location {
+                (anchor, self.text_location)
+            } else {
+                (self.text_location, anchor)
+            }
+        })
+    }
+    fn clear_selection(&mut self) {
+        if self.selection_anchor.take().is_some() {
+            self.set_needs_redraw(true);
+        }
+    }
+    // Deletes the selected text and places the caret where it started.
+    // Returns true if there was anything to delete.
+    fn delete_selection(&mut self) -> bool {
+        let Some((start, end)) = self.selection() else {
+            return false;
+        };
+        self.clear_selection();
+        if start == end {
+            return false;
+        }
+        self.buffer.borrow_mut().delete_range(start, end);
+        self.text_location = start;
+        self.scroll_text_location_into_view();
+        true
+    }
+    // Determines which graphemes of the given line are selected, as a byte range of that line.
+    fn selection_annotation(&self, line_idx: usize, line: &Line) -> Option<Annotation> {
+        let (start, end) = self.selection()?;
+        if line_idx < start.line_idx || line_idx > end.line_idx {
+            return None;
+        }
+        let start_idx = if line_idx == start.line_idx {
+            start.grapheme_idx
+        } else {
+            0
+        };
+        let end_idx = if line_idx == end.line_idx {
+            end.grapheme_idx
+        } else {
+            line.grapheme_count()
+        };
+        Some(Annotation {
+            kind: AnnotationType::Selection,
+            start: line.grapheme_idx_to_byte_idx(start_idx),
+            end: line.grapheme_idx_to_byte_idx(end_idx),
+        })
+    }
+
+    // Copies the selection, or the whole current line if nothing is selected.
+    pub fn copy(&self) -> Option<ClipboardContent> {
+        if let Some((start, end)) = self.selection().filter(|(start, end)| start != end) {
+            return Some(ClipboardContent {
+                text: self.buffer.borrow().get_text(start, end),
+                linewise: false,
+            });
+        }
+        self.buffer
+            .borrow()
+            .lines
+            .get(self.text_location.line_idx)
+            .map(|line| ClipboardContent {
+                text: format!("{line}\n"),
+                linewise: true,
+            })
+    }
+    // Cuts the selection, or the whole current line if nothing is selected.
+    pub fn cut(&mut self) -> Option<ClipboardContent> {
+        self.follow_buffer_changes();
+        let content = self.copy()?;
+        self.preferred_col = None;
+        self.buffer.borrow_mut().begin_step(self.snapshot(), false);
+        if !self.delete_selection() {
+            self.delete_current_line();
         }
+        self.skip_own_changes();
+        self.set_needs_redraw(true);
+        Some(content)
+    }
+    pub fn paste(&mut self, content: &ClipboardContent) {
+        self.follow_buffer_changes();
+        self.preferred_col = None;
+        self.buffer.borrow_mut().begin_step(self.snapshot(), false);
+        let at = if !self.delete_selection() && content.linewise {
+            Location {
+                line_idx: self.text_location.line_idx,
+                grapheme_idx: 0,
+            }
+        } else {
+            self.text_location
+        };
+        self.text_location = self.buffer.borrow_mut().insert_str(&content.text, at);
+        self.skip_own_changes();
+        self.scroll_text_location_into_view();
+        self.set_needs_redraw(true);
+    }
+    fn delete_current_line(&mut self) {
+        let line_idx = self.text_location.line_idx;
+        let line_end = |idx: usize| Location {
+            line_idx: idx,
+            grapheme_idx: self
+                .buffer
+                .borrow()
+                .lines
+                .get(idx)
+                .map_or(0, Line::grapheme_count),
+        };
+        // Removes the line together with its line break. The last line has no line break of its own,
+        // so the one of the line above is removed instead.
+        let (start, end) = if line_idx.saturating_add(1) < self.buffer.borrow().height() {
+            (
+                Location {
+                    line_idx,
+                    grapheme_idx: 0,
+                },
+                Location {
+                    line_idx: line_idx.saturating_add(1),
+                    grapheme_idx: 0,
+                },
+            )
+        } else if line_idx > 0 {
+            (line_end(line_idx.saturating_sub(1)), line_end(line_idx))
+        } else {
+            (Location::default(), line_end(line_idx))
+        };
+        self.buffer.borrow_mut().delete_range(start, end);
+        self.text_location.line_idx = start.line_idx;
+        self.snap_to_valid_grapheme();
         self.scroll_text_location_into_view();
     }
 
+    const fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            location: self.text_location,
+            scroll_offset: self.scroll_offset,
+        }
+    }
+    fn undo(&mut self) {
+        let snapshot = self.buffer.borrow_mut().undo();
+        if let Some(snapshot) = snapshot {
+            self.text_location = snapshot.location;
+            self.scroll_offset = snapshot.scroll_offset;
+            self.snap_to_valid_line();
+            self.snap_to_valid_grapheme();
+            self.scroll_text_location_into_view(); // the terminal might have been resized since the snapshot was taken
+            self.set_needs_redraw(true);
+        }
+    }
+    fn redo(&mut self) {
+        let location = self.buffer.borrow_mut().redo();
+        if let Some(location) = location {
+            self.text_location = location;
+            self.snap_to_valid_line();
+            self.snap_to_valid_grapheme();
+            self.scroll_text_location_into_view();
+            self.set_needs_redraw(true);
+        }
+    }
+
     fn insert_newline(&mut self) {
-        self.buffer.insert_newline(self.text_location);
-        self.handle_move_command(Move::Right);
+        self.text_location = self.buffer.borrow_mut().insert_newline(self.text_location);
+        self.scroll_text_location_into_view();
         self.set_needs_redraw(true);
     }
     fn delete_backward(&mut self) {
-        if self.text_location.line_idx != 0 || self.text_location.grapheme_idx != 0 {
-            self.handle_move_command(Move::Left);
+        if let Some(indent_stop) = self.previous_indent_stop() {
+            self.buffer.borrow_mut().delete_range(indent_stop, self.text_location);
+            self.text_location = indent_stop;
+            self.scroll_text_location_into_view();
+            self.set_needs_redraw(true);
+        } else if self.text_location.line_idx != 0 || self.text_location.grapheme_idx != 0 {
+            self.move_text_location(Move::Left);
             self.delete();
         }
     }
     fn delete(&mut self) {
-        self.buffer.delete(self.text_location);
+        self.buffer.borrow_mut().delete(self.text_location);
+        self.set_needs_redraw(true);
+    }
+    fn delete_word_backward(&mut self) {
+        let end = self.text_location;
+        self.move_text_location(Move::WordLeft);
+        self.buffer.borrow_mut().delete_range(self.text_location, end);
+        self.set_needs_redraw(true);
+    }
+    fn delete_word(&mut self) {
+        let start = self.text_location;
+        self.move_word_right();
+        let end = self.text_location;
+        self.text_location = start;
+        self.buffer.borrow_mut().delete_range(start, end);
+        self.set_needs_redraw(true);
+    }
+    // Removes one level of indentation from the caret line if it's blank, before a closing bracket is typed.
+    fn dedent_blank_line(&mut self) {
+        let level = self.buffer.borrow().last_indentation_level(self.text_location);
+        if let Some(level) = level {
+            self.buffer.borrow_mut().delete_range(level, self.text_location);
+            self.text_location = level;
+        }
+    }
+    // With soft tabs, inserts spaces up to the next indent stop instead of a tab.
+    fn insert_tab(&mut self) {
+        let (use_tabs, indent_width) = {
+            let settings = &self.buffer.borrow().settings;
+            (settings.use_tabs, settings.indent_width)
+        };
+        if use_tabs {
+            self.insert_char('\t');
+            return;
+        }
+        let col = self.current_col();
+        let count = indent_width.saturating_sub(col.checked_rem(indent_width).unwrap_or(0));
+        self.text_location = self
+            .buffer
+            .borrow_mut()
+            .insert_str(&" ".repeat(count), self.text_location);
+        self.scroll_text_location_into_view();
+        self.set_needs_redraw(true);
+    }
+    fn selects_multiple_lines(&self) -> bool {
+        self.selection()
+            .is_some_and(|(start, end)| start.line_idx != end.line_idx)
+    }
+    // Indents or dedents every line touched by the selection, or the caret line without one, by one level.
+    // The caret and the selection anchor move along with the text, so the selection keeps covering the lines.
+    fn shift_lines(&mut self, indent: bool) {
+        let (start, end) = self
+            .selection()
+            .unwrap_or((self.text_location, self.text_location));
+        // A selection ending at the start of a line doesn't include that line
+        let last_line_idx = if end.grapheme_idx == 0 && end.line_idx > start.line_idx {
+            end.line_idx.saturating_sub(1)
+        } else {
+            end.line_idx
+        };
+        for line_idx in start.line_idx..=last_line_idx {
+            let count = if indent {
+                self.buffer.borrow_mut().indent_line(line_idx)
+            } else {
+                self.buffer.borrow_mut().dedent_line(line_idx)
+            };
+            for location in
+                iter::once(&mut self.text_location).chain(self.selection_anchor.as_mut())
+            {
+                if location.line_idx == line_idx && location.grapheme_idx > 0 {
+                    location.grapheme_idx = if indent {
+                        location.grapheme_idx.saturating_add(count)
+                    } else {
+                        location.grapheme_idx.saturating_sub(count)
+                    };
+                }
+            }
+        }
+        self.scroll_text_location_into_view();
         self.set_needs_redraw(true);
     }
+    // With soft tabs and only spaces before the caret, Backspace deletes back to the previous indent stop.
+    fn previous_indent_stop(&self) -> Option<Location> {
+        let buffer = self.buffer.borrow();
+        let Location {
+            line_idx,
+            grapheme_idx,
+        } = self.text_location;
+        if buffer.settings.use_tabs || grapheme_idx == 0 {
+            return None;
+        }
+        let line = buffer.lines.get(line_idx)?;
+        let before = line.get(..line.grapheme_idx_to_byte_idx(grapheme_idx))?;
+        if !before.chars().all(|ch| ch == ' ') {
+            return None;
+        }
+        // Spaces take up one column each, so the caret column is its grapheme index
+        let count = grapheme_idx
+            .saturating_sub(1)
+            .checked_rem(buffer.settings.indent_width)
+            .map_or(1, |rem| rem.saturating_add(1));
+        Some(Location {
+            line_idx,
+            grapheme_idx: grapheme_idx.saturating_sub(count),
+        })
+    }
     fn insert_char(&mut self, character: char) {
+        if self.buffer.borrow().file_info.get_file_type().closes_block(character) {
+            self.dedent_blank_line();
+        }
         let old_len = self
             .buffer
+            .borrow()
             .lines
             .get(self.text_location.line_idx)
             .map_or(0, Line::grapheme_count);
-        self.buffer.insert_char(character, self.text_location);
+        self.buffer.borrow_mut().insert_char(character, self.text_location);
         let new_len = self
             .buffer
+            .borrow()
             .lines
             .get(self.text_location.line_idx)
             .map_or(0, Line::grapheme_count);
         let grapheme_delta = new_len.saturating_sub(old_len);
         if grapheme_delta > 0 {
             //move right for an added grapheme (should be the regular case)
-            self.handle_move_command(Move::Right);
+            self.move_text_location(Move::Right);
         }
         self.set_needs_redraw(true);
     }
 
 
-    fn render_line(at: usize, line_text: &str) -> Result<(), Error> {
-        Terminal::print_row(at, line_text)
+    // Views don't necessarily span the whole terminal width, so instead of clearing the row,
+    // the remainder of the view's width is overwritten with blanks.
+    fn render_line(at: Position, width: usize, line_text: &str) -> Result<(), Error> {
+        Terminal::print_at(at, &format!("{line_text:width$.width$}"))
+    }
+    fn render_gutter(at: Position, gutter_width: usize, number: &str) -> Result<(), Error> {
+        let digits = gutter_width.saturating_sub(1);
+        let mut gutter = AnnotatedString::default();
+        gutter.push_str(&format!("{number:>digits$}"), Some(AnnotationType::LineNumber));
+        gutter.push_str(" ", None);
+        Terminal::print_annotated_at(at, &gutter)
+    }
+    // The number shown in the gutter for the given line
+    const fn line_number(&self, line_idx: usize) -> usize {
+        let caret_line = self.text_location.line_idx;
+        match self.line_numbers {
+            LineNumbers::Relative if line_idx != caret_line => line_idx.abs_diff(caret_line),
+            _ => line_idx.saturating_add(1),
+        }
+    }
+    fn render_annotated_line(
+        at: Position,
+        padding: usize,
+        line_text: &AnnotatedString,
+    ) -> Result<(), Error> {
+        Terminal::print_annotated_at(at, line_text)?;
+        Terminal::print(&" ".repeat(padding))
     }
     fn build_welcome_message(width: usize) -> String {
         if width == 0 {
@@ -225,7 +989,7 @@ impl View {
         }
     }
     fn scroll_horizontally(&mut self, to: Col) {
-        let Size { width, .. } = self.size;
+        let width = self.text_width();
         let offset_changed = if to < self.scroll_offset.col {
             self.scroll_offset.col = to;
             true
@@ -240,12 +1004,32 @@ impl View {
         }
     }
     fn scroll_text_location_into_view(&mut self) {
+        if self.soft_wrap {
+            self.scroll_visual_row_into_view();
+            return;
+        }
         let Position { row, col } = self.text_location_to_position();
         self.scroll_vertically(row);
         self.scroll_horizontally(col);
     }
+    fn scroll_visual_row_into_view(&mut self) {
+        let height = self.size.height;
+        let caret_row = self.visual_row(self.text_location);
+        let top = self.top_visual_row();
+        if caret_row < top {
+            self.set_top_visual_row(caret_row);
+        } else if self.visual_distance(top, caret_row, height) >= height {
+            self.set_top_visual_row(self.visual_row_before(caret_row, height.saturating_sub(1)));
+        }
+    }
     fn center_text_location(&mut self) {
-        let Size { height, width } = self.size;
+        if self.soft_wrap {
+            let caret_row = self.visual_row(self.text_location);
+            self.set_top_visual_row(self.visual_row_before(caret_row, self.size.height.div_ceil(2)));
+            return;
+        }
+        let height = self.size.height;
+        let width = self.text_width();
         let Position { row, col } = self.text_location_to_position();
         let vertical_mid = height.div_ceil(2);
         let horizontal_mid = width.div_ceil(2);
@@ -255,32 +1039,234 @@ impl View {
     }
 
 
+    // The grapheme indices the rows of the given line start at in soft wrap mode
+    fn row_starts(&self, line_idx: usize) -> Rc<[usize]> {
+        let buffer = self.buffer.borrow();
+        let wrapped_for = (buffer.revision, self.text_width(), buffer.settings.tab_width);
+        let mut row_starts = self.row_starts.borrow_mut();
+        if row_starts.wrapped_for != wrapped_for {
+            row_starts.wrapped_for = wrapped_for;
+            row_starts.lines.clear();
+        }
+        let starts = row_starts.lines.entry(line_idx).or_insert_with(|| {
+            buffer.lines.get(line_idx).map_or_else(
+                || Rc::from([0]),
+                |line| line.wrap(wrapped_for.1, wrapped_for.2).into(),
+            )
+        });
+        Rc::clone(starts)
+    }
+    fn tab_width(&self) -> usize {
+        self.buffer.borrow().settings.tab_width
+    }
+    // The columns of the line shown in the given visual row
+    fn visual_row_columns(&self, buffer: &Buffer, row: VisualRow) -> (usize, usize) {
+        let row_starts = self.row_starts(row.line_idx);
+        buffer.lines.get(row.line_idx).map_or((0, 0), |line| {
+            let start = row_starts.get(row.row_idx).copied().unwrap_or(0);
+            let end = row_starts
+                .get(row.row_idx.saturating_add(1))
+                .copied()
+                .unwrap_or_else(|| line.grapheme_count());
+            let left = line.width_until(start, buffer.settings.tab_width);
+            let right = line.width_until(end, buffer.settings.tab_width);
+            // A grapheme wider than the view takes up a row of its own, and is clipped to fit
+            (left, min(right, left.saturating_add(self.text_width())))
+        })
+    }
+    fn visual_row(&self, location: Location) -> VisualRow {
+        let row_idx = self
+            .row_starts(location.line_idx)
+            .iter()
+            .rposition(|&start| start <= location.grapheme_idx)
+            .unwrap_or(0);
+        VisualRow {
+            line_idx: location.line_idx,
+            row_idx,
+        }
+    }
+    // The width of the text before the given location, within its visual row
+    fn width_in_visual_row(&self, location: Location) -> usize {
+        let VisualRow { line_idx, row_idx } = self.visual_row(location);
+        let row_start = self.row_starts(line_idx).get(row_idx).copied().unwrap_or(0);
+        let tab_width = self.tab_width();
+        self.buffer.borrow().lines.get(line_idx).map_or(0, |line| {
+            line.width_until(location.grapheme_idx, tab_width)
+                .saturating_sub(line.width_until(row_start, tab_width))
+        })
+    }
+    fn top_visual_row(&self) -> VisualRow {
+        let line_idx = self.scroll_offset.row;
+        // The top line might have been wrapped into fewer rows since it was scrolled to
+        let last_row_idx = self.row_starts(line_idx).len().saturating_sub(1);
+        VisualRow {
+            line_idx,
+            row_idx: min(self.wrap_top_row, last_row_idx),
+        }
+    }
+    fn set_top_visual_row(&mut self, row: VisualRow) {
+        self.scroll_offset.row = row.line_idx;
+        self.wrap_top_row = row.row_idx;
+        self.set_needs_redraw(true);
+    }
+    fn previous_visual_row(&self, row: VisualRow) -> Option<VisualRow> {
+        if let Some(row_idx) = row.row_idx.checked_sub(1) {
+            Some(VisualRow {
+                line_idx: row.line_idx,
+                row_idx,
+            })
+        } else {
+            let line_idx = row.line_idx.checked_sub(1)?;
+            Some(VisualRow {
+                line_idx,
+                row_idx: self.row_starts(line_idx).len().saturating_sub(1),
+            })
+        }
+    }
+    fn next_visual_row(&self, row: VisualRow) -> Option<VisualRow> {
+        let row_idx = row.row_idx.saturating_add(1);
+        if row_idx < self.row_starts(row.line_idx).len() {
+            Some(VisualRow {
+                line_idx: row.line_idx,
+                row_idx,
+            })
+        } else if row.line_idx < self.buffer.borrow().height() {
+            Some(VisualRow {
+                line_idx: row.line_idx.saturating_add(1),
+                row_idx: 0,
+            })
+        } else {
+            None
+        }
+    }
+    fn visual_row_before(&self, row: VisualRow, count: usize) -> VisualRow {
+        let mut result = row;
+        for _ in 0..count {
+            match self.previous_visual_row(result) {
+                Some(previous) => result = previous,
+                None => break,
+            }
+        }
+        result
+    }
+    // Counts the visual rows from `from` down to `to`, but not beyond `limit`
+    fn visual_distance(&self, from: VisualRow, to: VisualRow, limit: usize) -> usize {
+        let mut row = from;
+        let mut distance: usize = 0;
+        while row < to && distance < limit {
+            match self.next_visual_row(row) {
+                Some(next) => row = next,
+                None => break,
+            }
+            distance = distance.saturating_add(1);
+        }
+        distance
+    }
+    // Moves the caret by the given number of visual rows, keeping its column as far as possible.
+    fn move_visually(&mut self, step: usize, advance: fn(&Self, VisualRow) -> Option<VisualRow>) {
+        let width = self.preferred_col(self.width_in_visual_row(self.text_location));
+        let mut row = self.visual_row(self.text_location);
+        for _ in 0..step {
+            match advance(self, row) {
+                Some(next) => row = next,
+                None => break,
+            }
+        }
+        let row_starts = self.row_starts(row.line_idx);
+        let row_start = row_starts.get(row.row_idx).copied().unwrap_or(0);
+        let grapheme_idx = self.buffer.borrow().lines.get(row.line_idx).map_or(0, |line| {
+            // The caret can't be placed at the start of the next row, as it would show up there
+            let row_end = row_starts
+                .get(row.row_idx.saturating_add(1))
+                .map_or(line.grapheme_count(), |next_start| next_start.saturating_sub(1));
+            line.grapheme_idx_at_width(row_start, row_end, width, self.tab_width())
+        });
+        self.text_location = Location {
+            line_idx: row.line_idx,
+            grapheme_idx,
+        };
+        self.preferred_col = Some((self.text_location, width));
+    }
+    // The visual column to keep when moving vertically, if the caret hasn't been moved otherwise since the last vertical move.
+    // Otherwise, the given current column becomes the preferred one.
+    fn preferred_col(&self, current_col: usize) -> usize {
+        self.preferred_col
+            .filter(|(location, _)| *location == self.text_location)
+            .map_or(current_col, |(_, col)| col)
+    }
+    // Places the caret on its line at the preferred visual column, or before the grapheme covering it.
+    fn move_to_col(&mut self, col: usize) {
+        self.text_location.grapheme_idx = self
+            .buffer
+            .borrow()
+            .lines
+            .get(self.text_location.line_idx)
+            .map_or(0, |line| {
+                line.grapheme_idx_at_width(0, line.grapheme_count(), col, self.tab_width())
+            });
+        self.preferred_col = Some((self.text_location, col));
+    }
+    fn current_col(&self) -> usize {
+        self.buffer
+            .borrow()
+            .lines
+            .get(self.text_location.line_idx)
+            .map_or(0, |line| {
+                line.width_until(self.text_location.grapheme_idx, self.tab_width())
+            })
+    }
+
     pub fn caret_position(&self) -> Position {
-        self.text_location_to_position()
-            .saturating_sub(self.scroll_offset)
+        let Position { row, col } = if self.soft_wrap {
+            let caret_row = self.visual_row(self.text_location);
+            Position {
+                row: self.visual_distance(self.top_visual_row(), caret_row, self.size.height),
+                col: self.width_in_visual_row(self.text_location),
+            }
+        } else {
+            self.text_location_to_position()
+                .saturating_sub(self.scroll_offset)
+        };
+        Position {
+            row,
+            col: col.saturating_add(self.gutter_width()),
+        }
     }
 
     fn text_location_to_position(&self) -> Position {
         let row = self.text_location.line_idx;
-        debug_assert!(row.saturating_sub(1) <= self.buffer.lines.len());
+        debug_assert!(row.saturating_sub(1) <= self.buffer.borrow().lines.len());
         let col = self
             .buffer
+            .borrow()
             .lines
             .get(row)
-            .map_or(0, |line| line.width_until(self.text_location.grapheme_idx));
+            .map_or(0, |line| {
+                line.width_until(self.text_location.grapheme_idx, self.tab_width())
+            });
         Position { col, row }
     }
 
 
 
     fn move_up(&mut self, step: usize) {
+        if self.soft_wrap {
+            self.move_visually(step, Self::previous_visual_row);
+            return;
+        }
+        let col = self.preferred_col(self.current_col());
         self.text_location.line_idx = self.text_location.line_idx.saturating_sub(step);
-        self.snap_to_valid_grapheme();
+        self.move_to_col(col);
     }
     fn move_down(&mut self, step: usize) {
+        if self.soft_wrap {
+            self.move_visually(step, Self::next_visual_row);
+            return;
+        }
+        let col = self.preferred_col(self.current_col());
         self.text_location.line_idx = self.text_location.line_idx.saturating_add(step);
-        self.snap_to_valid_grapheme();
         self.snap_to_valid_line();
+        self.move_to_col(col);
     }
     // clippy::arithmetic_side_effects: This function performs arithmetic calculations
     // after explicitly checking that the target value will be within bounds.
@@ -288,14 +1274,19 @@ impl View {
     fn move_right(&mut self) {
         let line_width = self
             .buffer
+            .borrow()
             .lines
             .get(self.text_location.line_idx)
             .map_or(0, Line::grapheme_count);
         if self.text_location.grapheme_idx < line_width {
             self.text_location.grapheme_idx += 1;
         } else {
-            self.move_to_start_of_line();
-            self.move_down(1);
+            // Not move_down, which moves by visual row with soft wrap and would stay on a wrapped line
+            self.text_location = Location {
+                line_idx: self.text_location.line_idx.saturating_add(1),
+                grapheme_idx: 0,
+            };
+            self.snap_to_valid_line();
         }
     }
     // clippy::arithmetic_side_effects: This function performs arithmetic calculations
@@ -309,12 +1300,87 @@ impl View {
             self.move_to_end_of_line();
         }
     }
+    // Moves to the start of the word before the caret. At the start of a line, moves to the end of the previous one.
+    fn move_word_left(&mut self) {
+        if self.text_location.grapheme_idx == 0 {
+            self.move_left();
+            return;
+        }
+        self.text_location.grapheme_idx = self
+            .buffer
+            .borrow()
+            .lines
+            .get(self.text_location.line_idx)
+            .map_or(0, |line| line.previous_word_start(self.text_location.grapheme_idx));
+    }
+    // Moves to the end of the word after the caret. At the end of a line, moves to the start of the next one.
+    fn move_word_right(&mut self) {
+        let next_word_end = self
+            .buffer
+            .borrow()
+            .lines
+            .get(self.text_location.line_idx)
+            .filter(|line| self.text_location.grapheme_idx < line.grapheme_count())
+            .map(|line| line.next_word_end(self.text_location.grapheme_idx));
+        if let Some(grapheme_idx) = next_word_end {
+            self.text_location.grapheme_idx = grapheme_idx;
+        } else {
+            self.move_right();
+        }
+    }
     fn move_to_start_of_line(&mut self) {
         self.text_location.grapheme_idx = 0;
     }
+    fn move_to_start_of_buffer(&mut self) {
+        self.text_location = Location::default();
+    }
+    fn move_to_end_of_buffer(&mut self) {
+        self.text_location.line_idx = self.buffer.borrow().height().saturating_sub(1);
+        self.move_to_end_of_line();
+    }
+    // Moves to the blank line before the paragraph, skipping blank lines at the caret first.
+    // Without such a line, moves to the start of the buffer.
+    fn move_to_previous_paragraph(&mut self) {
+        let buffer = self.buffer.borrow();
+        let mut line_idx = self.text_location.line_idx;
+        while line_idx > 0 && buffer.is_blank_line(line_idx) {
+            line_idx = line_idx.saturating_sub(1);
+        }
+        while line_idx > 0 && !buffer.is_blank_line(line_idx) {
+            line_idx = line_idx.saturating_sub(1);
+        }
+        drop(buffer);
+        self.text_location = Location {
+            line_idx,
+            grapheme_idx: 0,
+        };
+    }
+    // Moves to the blank line after the paragraph, skipping blank lines at the caret first.
+    // Without such a line, moves to the end of the buffer.
+    fn move_to_next_paragraph(&mut self) {
+        let buffer = self.buffer.borrow();
+        let height = buffer.height();
+        let mut line_idx = self.text_location.line_idx;
+        while line_idx < height && buffer.is_blank_line(line_idx) {
+            line_idx = line_idx.saturating_add(1);
+        }
+        while line_idx < height && !buffer.is_blank_line(line_idx) {
+            line_idx = line_idx.saturating_add(1);
+        }
+        drop(buffer);
+        if line_idx < height {
+            self.text_location = Location {
+                line_idx,
+                grapheme_idx: 0,
+            };
+        } else {
+            self.move_to_end_of_buffer();
+        }
+    }
     fn move_to_end_of_line(&mut self) {
         self.text_location.grapheme_idx = self
             .buffer
+            .borrow()
             .lines
             .get(self.text_location.line_idx)
             .map_or(0, Line::grapheme_count);
@@ -325,16 +1391,65 @@ impl View {
     fn snap_to_valid_grapheme(&mut self) {
         self.text_location.grapheme_idx = self
             .buffer
+            .borrow()
             .lines
             .get(self.text_location.line_idx)
             .map_or(0, |line| {
                 min(line.grapheme_count(), self.text_location.grapheme_idx)
             });
     }
+    // Other views on the same buffer might have removed text since this view was last used.
+    // Moves the caret, the selection anchor and the top line along with the changes made through other views
+    // on the same buffer, so they stay on the same text.
+    fn follow_buffer_changes(&mut self) {
+        let revision = self.buffer.borrow().revision;
+        if revision == self.synced_revision {
+            return;
+        }
+        if let Some(changes) = self.buffer.borrow().changes_since(self.synced_revision) {
+            let mut top = Location {
+                line_idx: self.scroll_offset.row,
+                grapheme_idx: 0,
+            };
+            for change in changes {
+                self.text_location = change.adjust(self.text_location);
+                self.selection_anchor = self.selection_anchor.map(|anchor| change.adjust(anchor));
+                top = change.adjust(top);
+            }
+            if top.line_idx != self.scroll_offset.row {
+                self.scroll_offset.row = top.line_idx;
+                self.wrap_top_row = 0;
+            }
+        }
+        self.synced_revision = revision;
+        self.clamp_to_buffer();
+    }
+    // Called after editing through this view, which keeps its locations in line with its own changes.
+    fn skip_own_changes(&mut self) {
+        self.synced_revision = self.buffer.borrow().revision;
+    }
+    // Ensures the caret and the selection anchor still point to valid locations.
+    fn clamp_to_buffer(&mut self) {
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        if let Some(anchor) = self.selection_anchor {
+            let buffer = self.buffer.borrow();
+            let line_idx = min(anchor.line_idx, buffer.height());
+            let grapheme_idx = buffer
+                .lines
+                .get(line_idx)
+                .map_or(0, |line| min(anchor.grapheme_idx, line.grapheme_count()));
+            self.selection_anchor = Some(Location {
+                line_idx,
+                grapheme_idx,
+            });
+        }
+    }
     // Ensures self.location.line_idx points to a valid line index by snapping it to the bottom most line if appropriate.
     // Doesn't trigger scrolling.
     fn snap_to_valid_line(&mut self) {
-        self.text_location.line_idx = min(self.text_location.line_idx, self.buffer.height());
+        self.text_location.line_idx =
+            min(self.text_location.line_idx, self.buffer.borrow().height());
     }
 
 }
@@ -346,34 +1461,374 @@ impl UIComponent for View {
 
     fn needs_redraw(&self) -> bool {
         self.needs_redraw
+            || self.buffer.borrow().revision != self.buffer_revision
+            || (self.line_numbers == LineNumbers::Relative
+                && self.text_location.line_idx != self.drawn_caret_line)
     }
     fn set_size(&mut self, size: Size) {
         self.size = size;
         self.scroll_text_location_into_view();
     }
 
-    fn draw(&mut self, origin_row: usize) -> Result<(), Error> {
-        let Size { height, width } = self.size;
-        let end_y = origin_row.saturating_add(height);
+    fn draw(&mut self, origin: Position) -> Result<(), Error> {
+        let buffer_revision = self.buffer.borrow().revision;
+        if buffer_revision != self.buffer_revision {
+            // Another view might have changed the buffer
+            self.follow_buffer_changes();
+            self.buffer_revision = buffer_revision;
+        }
+        self.drawn_caret_line = self.text_location.line_idx;
+        let height = self.size.height;
+        let gutter_width = self.gutter_width();
+        let width = self.text_width();
         let top_third = height.div_ceil(3);
         let scroll_top = self.scroll_offset.row;
-        for current_row in origin_row..end_y {
-            // to get the correct line index, we have to take current_row (the absolute row on screen),
-            // subtract origin_row to get the current row relative to the view (ranging from 0 to self.size.height)
-            // and add the scroll offset.
-            let line_idx = current_row
-                .saturating_sub(origin_row)
-                .saturating_add(scroll_top);
-            if let Some(line) = self.buffer.lines.get(line_idx) {
+        self.buffer
+            .borrow_mut()
+            .highlight(scroll_top.saturating_add(height).saturating_sub(1));
+        let buffer = self.buffer.borrow();
+        let mut visual_row = self.soft_wrap.then(|| self.top_visual_row());
+        for view_row in 0..height {
+            let row = origin.row.saturating_add(view_row);
+            // In soft wrap mode, each line takes up as many rows as it has been wrapped into
+            let (line_idx, columns, is_first_row) = if self.soft_wrap {
+                visual_row.map_or((buffer.height(), 0..0, false), |visual_row| {
+                    let (left, right) = self.visual_row_columns(&buffer, visual_row);
+                    (visual_row.line_idx, left..right, visual_row.row_idx == 0)
+                })
+            } else {
                 let left = self.scroll_offset.col;
-                let right = self.scroll_offset.col.saturating_add(width);
-                Self::render_line(current_row, &line.get_visible_graphemes(left..right))?;
-            } else if current_row == top_third && self.buffer.is_empty() {
-                Self::render_line(current_row, &Self::build_welcome_message(width))?;
+                (
+                    view_row.saturating_add(scroll_top),
+                    left..left.saturating_add(width),
+                    true,
+                )
+            };
+            if gutter_width > 0 {
+                let number = if line_idx < buffer.height() && is_first_row {
+                    self.line_number(line_idx).to_string()
+                } else {
+                    String::new()
+                };
+                Self::render_gutter(
+                    Position {
+                        row,
+                        col: origin.col,
+                    },
+                    gutter_width,
+                    &number,
+                )?;
+            }
+            let at = Position {
+                row,
+                col: origin.col.saturating_add(gutter_width),
+            };
+            if let Some(line) = buffer.lines.get(line_idx) {
+                // The selection is added last, so it takes precedence over search matches and the syntax highlighting
+                let mut annotations = buffer.get_highlights(line_idx).to_vec();
+                annotations.extend(self.search_annotations(line_idx, line));
+                annotations.extend(self.selection_annotation(line_idx, line));
+                let tab_width = buffer.settings.tab_width;
+                let visible_width =
+                    min(line.width(tab_width), columns.end).saturating_sub(columns.start);
+                Self::render_annotated_line(
+                    at,
+                    width.saturating_sub(visible_width),
+                    &line.get_annotated_visible_substr(columns, &annotations, tab_width),
+                )?;
+            } else if view_row == top_third && buffer.is_empty() {
+                Self::render_line(at, width, &Self::build_welcome_message(width))?;
             } else {
-                Self::render_line(current_row, "~")?;
+                Self::render_line(at, width, "~")?;
             }
+            visual_row = visual_row.and_then(|visual_row| self.next_visual_row(visual_row));
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(file_name: &str, text: &str, caret: Location) -> View {
+        let mut view = View::new(Rc::new(RefCell::new(Buffer::from_text(file_name, text))));
+        view.text_location = caret;
+        view
+    }
+
+    fn text(view: &View) -> String {
+        let buffer = view.buffer.borrow();
+        buffer
+            .lines
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn type_char(file_name: &str, text_before: &str, caret: Location, character: char) -> String {
+        let mut view = view(file_name, text_before, caret);
+        view.handle_edit_command(Edit::Insert(character));
+        text(&view)
+    }
+
+    const fn location(line_idx: usize, grapheme_idx: usize) -> Location {
+        Location {
+            line_idx,
+            grapheme_idx,
+        }
+    }
+
+    #[test]
+    fn other_views_stay_on_the_same_text() {
+        let mut editing = view("a.txt", "one\ntwo\nthree", location(0, 0));
+        let mut other = editing.duplicate();
+        other.text_location = location(2, 3);
+        other.selection_anchor = Some(location(1, 1));
+        editing.handle_edit_command(Edit::Insert('x'));
+        editing.handle_edit_command(Edit::InsertNewline);
+        editing.follow_buffer_changes();
+        assert_eq!(editing.text_location, location(1, 0));
+
+        other.follow_buffer_changes();
+        assert_eq!(other.text_location, location(3, 3));
+        assert_eq!(other.selection_anchor, Some(location(2, 1)));
+
+        editing.handle_edit_command(Edit::Undo);
+        other.follow_buffer_changes();
+        assert_eq!(other.text_location, location(2, 3));
+        assert_eq!(text(&other), "xone\ntwo\nthree");
+
+        editing.text_location = location(1, 1);
+        editing.handle_edit_command(Edit::DeleteWordBackward);
+        other.follow_buffer_changes();
+        assert_eq!(other.text_location, location(2, 3));
+        assert_eq!(other.selection_anchor, Some(location(1, 0)));
+    }
+
+    #[test]
+    fn clips_graphemes_wider_than_the_view() {
+        let mut view = view("a.txt", "日本", location(0, 0));
+        view.size = Size {
+            width: 1,
+            height: 5,
+        };
+        let buffer = Rc::clone(&view.buffer);
+        let row = |row_idx| VisualRow {
+            line_idx: 0,
+            row_idx,
+        };
+        assert_eq!(view.visual_row_columns(&buffer.borrow(), row(0)), (0, 1));
+        assert_eq!(view.visual_row_columns(&buffer.borrow(), row(1)), (2, 3));
+    }
+
+    #[test]
+    fn wraps_lines_again_only_after_changes() {
+        let mut view = view("a.txt", "the quick brown fox", location(0, 0));
+        view.size = Size {
+            width: 10,
+            height: 5,
+        };
+        let rows = view.row_starts(0);
+        assert_eq!(*rows, [0, 10]);
+        assert!(Rc::ptr_eq(&rows, &view.row_starts(0)));
+        view.handle_edit_command(Edit::Insert('x'));
+        assert_eq!(*view.row_starts(0), [0, 5, 11]);
+        view.size.width = 5;
+        assert_eq!(*view.row_starts(0), [0, 5, 10, 11, 16]);
+    }
+
+    #[test]
+    fn moves_right_past_the_end_of_wrapped_lines() {
+        let mut view = view("a.txt", "the quick brown fox\nnext", location(0, 19));
+        view.size = Size {
+            width: 10,
+            height: 5,
+        };
+        view.soft_wrap = true;
+        view.move_right();
+        assert_eq!(view.text_location, location(1, 0));
+        view.text_location = location(0, 16);
+        view.move_word_right();
+        view.move_word_right();
+        assert_eq!(view.text_location, location(1, 0));
+    }
+
+    #[test]
+    fn gutter_fits_into_narrow_panes() {
+        let mut view = view("a.txt", &"x\n".repeat(999), location(998, 1));
+        view.line_numbers = LineNumbers::Absolute;
+        view.size = Size {
+            width: 6,
+            height: 5,
+        };
+        assert_eq!(view.gutter_width(), 5);
+        assert_eq!(view.text_width(), 1);
+        for width in [5, 1, 0] {
+            view.size.width = width;
+            view.scroll_text_location_into_view();
+            assert_eq!(view.gutter_width(), 0);
+            assert_eq!(view.text_width(), width);
+            assert!(view.caret_position().col <= width);
+        }
+    }
+
+    #[test]
+    fn go_to_checks_the_range() {
+        let mut view = view("a.txt", "one\ntwo\nthree\nfour", location(1, 0));
+        let mut go_to = |input: &str| {
+            GoToTarget::try_from(input)
+                .and_then(|target| view.go_to(target))
+                .map(|()| view.text_location)
+        };
+        assert_eq!(go_to("3"), Ok(location(2, 0)));
+        assert_eq!(go_to("3:6"), Ok(location(2, 5)));
+        assert_eq!(go_to("-2"), Ok(location(0, 0)));
+        assert_eq!(go_to("+3"), Ok(location(3, 0)));
+        assert_eq!(go_to("50%"), Ok(location(1, 0)));
+        assert_eq!(go_to("100%"), Ok(location(3, 0)));
+        assert_eq!(
+            go_to("0"),
+            Err(String::from("0 is out of range (lines 1-4)"))
+        );
+        assert_eq!(
+            go_to("5"),
+            Err(String::from("5 is out of range (lines 1-4)"))
+        );
+        assert_eq!(
+            go_to("+1"),
+            Err(String::from("+1 is out of range (lines 1-4)"))
+        );
+        assert_eq!(
+            go_to("-4"),
+            Err(String::from("-4 is out of range (lines 1-4)"))
+        );
+        assert_eq!(
+            go_to("101%"),
+            Err(String::from("101% is out of range (lines 1-4)"))
+        );
+        assert_eq!(
+            go_to("1:5"),
+            Err(String::from("1:5 is out of range (columns 1-4)"))
+        );
+        assert_eq!(
+            go_to("1:0"),
+            Err(String::from("1:0 is out of range (columns 1-4)"))
+        );
+        assert_eq!(go_to("x"), Err(String::from("Invalid location: x")));
+    }
+
+    fn count_matches(view: &mut View, query: &str) -> String {
+        view.search(
+            query,
+            SearchOptions {
+                regex: true,
+                ..SearchOptions::default()
+            },
+        );
+        view.match_count()
+            .map(|count| count.to_string())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn match_count_leaves_out_empty_matches() {
+        let mut view = view("a.txt", "baab\nb\nab", location(0, 0));
+        view.enter_search();
+        assert_eq!(count_matches(&mut view, "a*"), "1 of 2");
+        assert_eq!(count_matches(&mut view, "^"), "no matches");
+        assert_eq!(count_matches(&mut view, "b"), "1 of 4");
+        view.search_next();
+        assert_eq!(view.match_count().unwrap().to_string(), "2 of 4");
+    }
+
+    #[test]
+    fn match_count_follows_changes_of_the_text() {
+        let mut view = view("a.txt", "ab", location(0, 0));
+        view.enter_search();
+        assert_eq!(count_matches(&mut view, "a"), "1 of 1");
+        view.buffer.borrow_mut().insert_str("a", location(1, 0));
+        assert_eq!(view.match_count().unwrap().to_string(), "1 of 2");
+    }
+
+    fn start_replace(view: &mut View, query: &str, replacement: &str, regex: bool) {
+        let options = SearchOptions {
+            regex,
+            ..SearchOptions::default()
+        };
+        view.start_replace(query, replacement, options).unwrap();
+    }
+
+    #[test]
+    fn replaces_or_skips_each_match() {
+        let mut view = view("a.txt", "a a a", location(0, 0));
+        start_replace(&mut view, "a", "bb", false);
+        view.replace_current();
+        assert_eq!(
+            view.replace_info.as_ref().unwrap().current,
+            Some(location(0, 3)..location(0, 4))
+        );
+        view.skip_current();
+        view.replace_current();
+        assert!(!view.has_replacement());
+        assert_eq!(text(&view), "bb a bb");
+        assert_eq!(view.finish_replace(), 2);
+    }
+
+    #[test]
+    fn replacing_stops_at_the_origin_after_wrapping() {
+        let mut view = view("a.txt", "a a\na a", location(0, 2));
+        start_replace(&mut view, "a", "bb", false);
+        view.replace_all();
+        assert_eq!(text(&view), "bb bb\nbb bb");
+        assert_eq!(view.finish_replace(), 4);
+    }
+
+    #[test]
+    fn replaces_an_empty_match_at_the_origin_once() {
+        let mut view = view("a.txt", "one\ntwo\nthree", location(0, 0));
+        start_replace(&mut view, "^", "// ", true);
+        view.replace_all();
+        assert_eq!(text(&view), "// one\n// two\n// three");
+        assert_eq!(view.finish_replace(), 3);
+    }
+
+    #[test]
+    fn replace_all_is_undone_in_one_step() {
+        let mut view = view("a.txt", "x1 x2\nx3", location(0, 0));
+        view.handle_edit_command(Edit::Insert('y'));
+        start_replace(&mut view, r"x(\d)", "${1}x", true);
+        view.replace_all();
+        assert_eq!(text(&view), "y1x 2x\n3x");
+        assert_eq!(view.finish_replace(), 3);
+        view.handle_edit_command(Edit::Undo);
+        assert_eq!(text(&view), "yx1 x2\nx3");
+        view.handle_edit_command(Edit::Redo);
+        assert_eq!(text(&view), "y1x 2x\n3x");
+    }
+
+    #[test]
+    fn closing_bracket_dedents_blank_line() {
+        assert_eq!(
+            type_char("a.rs", "fn main() {\n    x();\n    ", location(2, 4), '}'),
+            "fn main() {\n    x();\n}"
+        );
+        assert_eq!(
+            type_char("a.c", "f(\n        a,\n        ", location(2, 8), ')'),
+            "f(\n        a,\n    )"
+        );
+        assert_eq!(
+            type_char("a.py", "v = [\n      ", location(1, 6), ']'),
+            "v = [\n    ]"
+        );
+    }
+
+    #[test]
+    fn closing_bracket_keeps_text_lines() {
+        assert_eq!(type_char("a.rs", "    x", location(0, 5), '}'), "    x}");
+        assert_eq!(type_char("a.rs", "    ", location(0, 2), '}'), "  }  ");
+        assert_eq!(type_char("a.md", "    ", location(0, 4), '}'), "    }");
+        assert_eq!(type_char("a.txt", "\t", location(0, 1), '}'), "\t}");
+    }
+}
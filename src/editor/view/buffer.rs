@@ -1,6 +1,14 @@
+use super::changelog::{ChangeLog, TextChange};
+use super::highlighter::{Highlighter, Syntax};
+use super::history::{Change, History, Snapshot};
+use super::Annotation;
+use super::Config;
 use super::FileInfo;
+use super::FileTypeSettings;
 use super::Line;
 use super::Location;
+use regex::Regex;
+use std::ops::Range;
 use std::fs::{read_to_string, File};
 use std::io::Error;
 use std::io::Write;
@@ -10,26 +18,62 @@ pub struct Buffer {
     pub lines: Vec<Line>,
     pub file_info: FileInfo,
     pub dirty: bool,
+    pub readonly: bool,
+    pub settings: FileTypeSettings,
+    pub revision: usize, // increases with every change of the text, so views can tell when to redraw
+    pub last_snapshot: Snapshot, // where the last view showing this buffer left off
+    history: History,
+    changes: ChangeLog,
+    highlighter: Highlighter,
 }
 
 impl Buffer {
-    pub fn load(file_name: &str) -> Result<Self, Error> {
+    pub fn load(file_name: &str, config: &Config) -> Result<Self, Error> {
         let contents = read_to_string(file_name)?;
         let mut lines = Vec::new();
         for value in contents.lines() {
             lines.push(Line::from(value));
         }
-        Ok(Self {
+        let mut buffer = Self {
             lines,
-            file_info: FileInfo::from(file_name),
-            dirty: false,
-        })
+            ..Self::default()
+        };
+        buffer.set_file_info(FileInfo::from(file_name), config);
+        Ok(buffer)
     }
 
-    pub fn search_forward(&self, query: &str, from: Location) -> Option<Location> {
-        if query.is_empty() {
-            return None;
-        }
+    #[cfg(test)]
+    pub fn from_text(file_name: &str, text: &str) -> Self {
+        let mut buffer = Self {
+            lines: text.split('\n').map(Line::from).collect(),
+            ..Self::default()
+        };
+        buffer.set_file_info(FileInfo::from(file_name), &Config::default());
+        buffer
+    }
+
+    // Switches to the given file info, re-detecting the file type and applying its settings.
+    fn set_file_info(&mut self, mut file_info: FileInfo, config: &Config) {
+        file_info.detect_file_type(&self.lines);
+        self.settings = config.settings_for(file_info.get_file_type());
+        FileInfo::apply_modeline_settings(&self.lines, &mut self.settings);
+        self.highlighter = Highlighter::new(
+            Syntax::for_file_type(file_info.get_file_type()),
+            self.settings.comment_token.clone(),
+        );
+        self.file_info = file_info;
+    }
+    // Makes sure the highlighting is up to date for all lines up to and including until_line_idx.
+    pub fn highlight(&mut self, until_line_idx: usize) {
+        self.highlighter.update(&self.lines, until_line_idx);
+    }
+    pub fn get_highlights(&self, line_idx: usize) -> &[Annotation] {
+        self.highlighter.get_annotations(line_idx)
+    }
+
+    // Finds the next match at or after the given location, wrapping around at the end of the buffer.
+    // Returns where the match starts and ends.
+    pub fn search_forward(&self, query: &Regex, from: Location) -> Option<Range<Location>> {
         let mut is_first = true;
         for (line_idx, line) in self
             .lines
@@ -45,19 +89,13 @@ impl Buffer {
             } else {
                 0
             };
-            if let Some(grapheme_idx) = line.search_forward(query, from_grapheme_idx) {
-                return Some(Location {
-                    grapheme_idx,
-                    line_idx,
-                });
+            if let Some(range) = line.search_forward(query, from_grapheme_idx) {
+                return Some(Self::line_range_to_locations(line_idx, range));
             }
         }
         None
     }
-    pub fn search_backward(&self, query: &str, from: Location) -> Option<Location> {
-        if query.is_empty() {
-            return None;
-        }
+    pub fn search_backward(&self, query: &Regex, from: Location) -> Option<Range<Location>> {
         let mut is_first = true;
         for (line_idx, line) in self
             .lines
@@ -74,15 +112,21 @@ impl Buffer {
             } else {
                 line.grapheme_count()
             };
-            if let Some(grapheme_idx) = line.search_backward(query, from_grapheme_idx) {
-                return Some(Location {
-                    grapheme_idx,
-                    line_idx,
-                });
+            if let Some(range) = line.search_backward(query, from_grapheme_idx) {
+                return Some(Self::line_range_to_locations(line_idx, range));
             }
         }
         None
     }
+    const fn line_range_to_locations(line_idx: usize, range: Range<usize>) -> Range<Location> {
+        Location {
+            line_idx,
+            grapheme_idx: range.start,
+        }..Location {
+            line_idx,
+            grapheme_idx: range.end,
+        }
+    }
 
     fn save_to_file(&self, file_info: &FileInfo) -> Result<(), Error> {
         if let Some(file_path) = &file_info.get_path() {
@@ -98,16 +142,18 @@ impl Buffer {
         }
         Ok(())
     }
-    pub fn save_as(&mut self, file_name: &str) -> Result<(), Error> {
+    pub fn save_as(&mut self, file_name: &str, config: &Config) -> Result<(), Error> {
         let file_info = FileInfo::from(file_name);
         self.save_to_file(&file_info)?;
-        self.file_info = file_info;
+        self.set_file_info(file_info, config);
+        self.history.mark_saved();
         self.dirty = false;
         Ok(())
     }
 
     pub fn save(&mut self) -> Result<(), Error> {
         self.save_to_file(&self.file_info)?;
+        self.history.mark_saved();
         self.dirty = false;
         Ok(())
     }
@@ -121,42 +167,462 @@ impl Buffer {
     pub fn height(&self) -> usize {
         self.lines.len()
     }
+    // Finds the start of the last level of indentation of a blank line, if the caret at the given location is behind it.
+    pub fn last_indentation_level(&self, at: Location) -> Option<Location> {
+        let line = self.lines.get(at.line_idx)?;
+        if !line.trim().is_empty()
+            || at.grapheme_idx == 0
+            || at.grapheme_idx != line.grapheme_count()
+        {
+            return None;
+        }
+        let count = if line.ends_with('\t') {
+            1
+        } else {
+            let spaces = line.chars().rev().take_while(|&ch| ch == ' ').count();
+            spaces
+                .saturating_sub(1)
+                .checked_rem(self.settings.indent_width)
+                .map_or(1, |rem| rem.saturating_add(1))
+        };
+        Some(Location {
+            line_idx: at.line_idx,
+            grapheme_idx: at.grapheme_idx.saturating_sub(count),
+        })
+    }
+    // Adds one level of indentation in front of the line and returns the number of graphemes added.
+    // Empty lines are skipped, so indenting a block doesn't leave trailing whitespace behind.
+    pub fn indent_line(&mut self, line_idx: usize) -> usize {
+        if self.lines.get(line_idx).is_none_or(|line| line.is_empty()) {
+            return 0;
+        }
+        let at = Location {
+            line_idx,
+            grapheme_idx: 0,
+        };
+        self.insert_str(&self.settings.indent_unit(), at).grapheme_idx
+    }
+    // Removes one level of indentation from the front of the line and returns the number of graphemes removed.
+    pub fn dedent_line(&mut self, line_idx: usize) -> usize {
+        let Some(line) = self.lines.get(line_idx) else {
+            return 0;
+        };
+        let count = if line.starts_with('\t') {
+            1
+        } else {
+            line.chars()
+                .take(self.settings.indent_width)
+                .take_while(|&ch| ch == ' ')
+                .count()
+        };
+        self.delete_range(
+            Location {
+                line_idx,
+                grapheme_idx: 0,
+            },
+            Location {
+                line_idx,
+                grapheme_idx: count,
+            },
+        );
+        count
+    }
+    pub fn is_blank_line(&self, line_idx: usize) -> bool {
+        self.lines
+            .get(line_idx)
+            .is_none_or(|line| line.trim().is_empty())
+    }
     pub fn insert_char(&mut self, character: char, at: Location) {
         debug_assert!(at.line_idx <= self.height());
-        if at.line_idx == self.height() {
-            self.lines.push(Line::from(&character.to_string()));
-            self.dirty = true;
-        } else if let Some(line) = self.lines.get_mut(at.line_idx) {
-            line.insert_char(character, at.grapheme_idx);
-            self.dirty = true;
-        }
+        self.insert_str(&character.to_string(), at);
     }
     pub fn delete(&mut self, at: Location) {
         if let Some(line) = self.lines.get(at.line_idx) {
-            if at.grapheme_idx >= line.grapheme_count()
+            let end = if at.grapheme_idx >= line.grapheme_count()
                 && self.height() > at.line_idx.saturating_add(1)
             {
-                let next_line = self.lines.remove(at.line_idx.saturating_add(1));
-                // clippy::indexing_slicing: We checked for existence of this line in the surrounding if statment
-                #[allow(clippy::indexing_slicing)]
-                self.lines[at.line_idx].append(&next_line);
-                self.dirty = true;
+                Location {
+                    line_idx: at.line_idx.saturating_add(1),
+                    grapheme_idx: 0,
+                }
             } else if at.grapheme_idx < line.grapheme_count() {
-                // clippy::indexing_slicing: We checked for existence of this line in the surrounding if statment
-                #[allow(clippy::indexing_slicing)]
-                self.lines[at.line_idx].delete(at.grapheme_idx);
-                self.dirty = true;
-            }
+                Location {
+                    line_idx: at.line_idx,
+                    grapheme_idx: at.grapheme_idx.saturating_add(1),
+                }
+            } else {
+                return;
+            };
+            self.delete_range(at, end);
+        }
+    }
+    // Splits the line at the given location, replacing the whitespace around it. The new line gets the
+    // leading whitespace of the split one up to the location, one level more if the text before it opens a block.
+    // Splitting at the start of a line keeps the line as it is, just moving it down.
+    // Returns the location behind the indentation of the new line.
+    pub fn insert_newline(&mut self, at: Location) -> Location {
+        let Some(line) = self.lines.get(at.line_idx).filter(|_| at.grapheme_idx > 0) else {
+            return self.insert_str("\n", at);
+        };
+        let byte_idx = line.grapheme_idx_to_byte_idx(at.grapheme_idx);
+        let before = line.get(..byte_idx).unwrap_or_default();
+        let after = line.get(byte_idx..).unwrap_or_default();
+        let mut indentation: String = before
+            .chars()
+            .take_while(|ch| ch.is_whitespace())
+            .collect();
+        if self.file_info.get_file_type().opens_block(before) {
+            indentation.push_str(&self.settings.indent_unit());
         }
+        // Whitespace graphemes consist of a single character each
+        let start = Location {
+            line_idx: at.line_idx,
+            grapheme_idx: at.grapheme_idx.saturating_sub(
+                before.chars().rev().take_while(|ch| ch.is_whitespace()).count(),
+            ),
+        };
+        let end = Location {
+            line_idx: at.line_idx,
+            grapheme_idx: at.grapheme_idx.saturating_add(
+                after.chars().take_while(|ch| ch.is_whitespace()).count(),
+            ),
+        };
+        self.delete_range(start, end);
+        self.insert_str(&format!("\n{indentation}"), start)
     }
-    pub fn insert_newline(&mut self, at: Location) {
+
+    // Inserts a (possibly multi-line) text at the given location as a single change.
+    // Returns the location right behind the inserted text.
+    pub fn insert_str(&mut self, text: &str, at: Location) -> Location {
+        if text.is_empty() || at.line_idx > self.height() {
+            return at;
+        }
         if at.line_idx == self.height() {
-            self.lines.push(Line::default());
-            self.dirty = true;
-        } else if let Some(line) = self.lines.get_mut(at.line_idx) {
-            let new = line.split(at.grapheme_idx);
-            self.lines.insert(at.line_idx.saturating_add(1), new);
-            self.dirty = true;
+            // The line behind the last one only comes into existence once something is inserted into it.
+            // Since every line is terminated by a newline when saved, a trailing newline of the text is already implied.
+            self.push_line();
+            if let Some(text) = text.strip_suffix('\n') {
+                self.insert_str(text, at);
+                return Location {
+                    line_idx: at.line_idx.saturating_add(1),
+                    grapheme_idx: 0,
+                };
+            }
+        }
+        let end = self.apply_insert(text, at);
+        self.record(Change::Insert {
+            at,
+            end,
+            text: text.to_string(),
+        });
+        end
+    }
+    // Returns all text between start (inclusive) and end (exclusive), with lines separated by newlines.
+    pub fn get_text(&self, start: Location, end: Location) -> String {
+        let mut result = String::new();
+        for (line_idx, line) in self
+            .lines
+            .iter()
+            .enumerate()
+            .take(end.line_idx.saturating_add(1))
+            .skip(start.line_idx)
+        {
+            let from = if line_idx == start.line_idx {
+                line.grapheme_idx_to_byte_idx(start.grapheme_idx)
+            } else {
+                0
+            };
+            let to = if line_idx == end.line_idx {
+                line.grapheme_idx_to_byte_idx(end.grapheme_idx)
+            } else {
+                line.len()
+            };
+            if line_idx > start.line_idx {
+                result.push('\n');
+            }
+            result.push_str(line.get(from..to).unwrap_or_default());
+        }
+        result
+    }
+    // Removes all text between start (inclusive) and end (exclusive) as a single change and returns it.
+    pub fn delete_range(&mut self, start: Location, end: Location) -> String {
+        if start.line_idx >= self.height() {
+            return String::new();
+        }
+        // A range ending on the line behind the last one ends with the last line.
+        let end = if end.line_idx >= self.height() {
+            let line_idx = self.height().saturating_sub(1);
+            Location {
+                line_idx,
+                grapheme_idx: self.lines.get(line_idx).map_or(0, Line::grapheme_count),
+            }
+        } else {
+            end
+        };
+        if start >= end {
+            return String::new();
+        }
+        let text = self.apply_remove(start, end);
+        self.record(Change::Remove {
+            at: start,
+            end,
+            text: text.clone(),
+        });
+        text
+    }
+    // The changes of the text made after the given revision, or None if they are no longer all known.
+    pub fn changes_since(&self, revision: usize) -> Option<impl Iterator<Item = TextChange> + '_> {
+        self.changes.since(revision)
+    }
+    fn push_line(&mut self) {
+        self.push_line_unrecorded();
+        self.record(Change::PushLine {
+            line_idx: self.height().saturating_sub(1),
+        });
+    }
+
+    // Called before the text is changed from the given line onward.
+    fn invalidate_from(&mut self, line_idx: usize) {
+        self.highlighter.invalidate_from(line_idx);
+        self.revision = self.revision.wrapping_add(1);
+    }
+    fn push_line_unrecorded(&mut self) {
+        self.invalidate_from(self.height());
+        self.lines.push(Line::default());
+    }
+
+    // clippy::indexing_slicing: All callers make sure at.line_idx points to an existing line
+    #[allow(clippy::indexing_slicing)]
+    fn apply_insert(&mut self, text: &str, at: Location) -> Location {
+        self.invalidate_from(at.line_idx);
+        let tail = self.lines[at.line_idx].split(at.grapheme_idx);
+        let mut line_idx = at.line_idx;
+        for (idx, part) in text.split('\n').enumerate() {
+            if idx > 0 {
+                line_idx = line_idx.saturating_add(1);
+                self.lines.insert(line_idx, Line::default());
+            }
+            self.lines[line_idx].append(&Line::from(part));
+        }
+        let end = Location {
+            line_idx,
+            grapheme_idx: self.lines[line_idx].grapheme_count(),
+        };
+        self.lines[line_idx].append(&tail);
+        self.changes
+            .push(self.revision, TextChange::Insert { at, end });
+        end
+    }
+    // clippy::indexing_slicing: All callers make sure start and end point to existing lines
+    #[allow(clippy::indexing_slicing)]
+    fn apply_remove(&mut self, start: Location, end: Location) -> String {
+        self.invalidate_from(start.line_idx);
+        let tail = self.lines[end.line_idx].split(end.grapheme_idx);
+        let mut removed = self.lines[start.line_idx].split(start.grapheme_idx).to_string();
+        if end.line_idx > start.line_idx {
+            for line in self
+                .lines
+                .drain(start.line_idx.saturating_add(1)..=end.line_idx)
+            {
+                removed.push('\n');
+                removed.push_str(&line);
+            }
+        }
+        self.lines[start.line_idx].append(&tail);
+        self.changes
+            .push(self.revision, TextChange::Remove { at: start, end });
+        removed
+    }
+
+    fn record(&mut self, change: Change) {
+        self.history.record(change);
+        self.dirty = self.history.is_modified();
+    }
+
+    pub fn begin_step(&mut self, before: Snapshot, coalesce: bool) {
+        self.history.begin_step(before, coalesce);
+    }
+    // Reverts the last step and returns the view state from before it was taken.
+    pub fn undo(&mut self) -> Option<Snapshot> {
+        let step = self.history.pop_undo()?;
+        for change in step.changes.iter().rev() {
+            match change {
+                Change::Insert { at, end, .. } => {
+                    self.apply_remove(*at, *end);
+                }
+                Change::Remove { at, text, .. } => {
+                    self.apply_insert(text, *at);
+                }
+                Change::PushLine { line_idx } => {
+                    self.invalidate_from(*line_idx);
+                    self.lines.pop();
+                }
+            }
+        }
+        let before = step.before;
+        self.history.push_redo(step);
+        self.dirty = self.history.is_modified();
+        Some(before)
+    }
+    // Re-applies the last undone step and returns the location right behind it.
+    pub fn redo(&mut self) -> Option<Location> {
+        let step = self.history.pop_redo()?;
+        for change in &step.changes {
+            match change {
+                Change::Insert { at, text, .. } => {
+                    self.apply_insert(text, *at);
+                }
+                Change::Remove { at, end, .. } => {
+                    self.apply_remove(*at, *end);
+                }
+                Change::PushLine { .. } => self.push_line_unrecorded(),
+            }
+        }
+        let location = step.changes.last().map(Change::caret_after);
+        self.history.push_undo(step);
+        self.dirty = self.history.is_modified();
+        location
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::AnnotationType;
+    use super::*;
+
+    fn text(buffer: &Buffer) -> String {
+        buffer
+            .lines
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn split(file_name: &str, line: &str, grapheme_idx: usize) -> (String, Location) {
+        let mut buffer = Buffer::from_text(file_name, line);
+        let location = buffer.insert_newline(Location {
+            line_idx: 0,
+            grapheme_idx,
+        });
+        (text(&buffer), location)
+    }
+
+    fn location(line_idx: usize, grapheme_idx: usize) -> Location {
+        Location {
+            line_idx,
+            grapheme_idx,
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn newline_keeps_indentation() {
+        assert_eq!(
+            split("a.rs", "    let x = 1;", 14),
+            (String::from("    let x = 1;\n    "), location(1, 4))
+        );
+        assert_eq!(
+            split("a.txt", "\tword", 5),
+            (String::from("\tword\n\t"), location(1, 1))
+        );
+    }
+
+    #[test]
+    fn newline_indents_after_opening_bracket() {
+        assert_eq!(
+            split("a.rs", "    fn main() {", 15),
+            (String::from("    fn main() {\n        "), location(1, 8))
+        );
+        assert_eq!(
+            split("a.c", "call(", 5),
+            (String::from("call(\n    "), location(1, 4))
+        );
+        assert_eq!(
+            split("a.py", "def f():", 8),
+            (String::from("def f():\n    "), location(1, 4))
+        );
+        assert_eq!(
+            split("a.md", "- item [", 8),
+            (String::from("- item [\n"), location(1, 0))
+        );
+    }
+
+    #[test]
+    fn newline_in_leading_whitespace_does_not_duplicate_it() {
+        assert_eq!(
+            split("a.rs", "    foo", 2),
+            (String::from("\n  foo"), location(1, 2))
+        );
+        assert_eq!(
+            split("a.rs", "    foo", 0),
+            (String::from("\n    foo"), location(1, 0))
+        );
+    }
+
+    #[test]
+    fn newline_replaces_whitespace_around_the_caret() {
+        assert_eq!(
+            split("a.rs", "    ", 4),
+            (String::from("\n    "), location(1, 4))
+        );
+        assert_eq!(
+            split("a.rs", "    foo   bar", 9),
+            (String::from("    foo\n    bar"), location(1, 4))
+        );
+    }
+
+    #[test]
+    fn newline_is_undone_in_one_step() {
+        let mut buffer = Buffer::from_text("a.rs", "    foo   bar");
+        buffer.begin_step(Snapshot::default(), false);
+        buffer.insert_newline(location(0, 9));
+        buffer.undo();
+        assert_eq!(text(&buffer), "    foo   bar");
+    }
+
+    #[test]
+    fn last_indentation_level_of_blank_lines() {
+        let buffer = Buffer::from_text("a.rs", "        \n      \n    x\n");
+        assert_eq!(
+            buffer.last_indentation_level(location(0, 8)),
+            Some(location(0, 4))
+        );
+        assert_eq!(
+            buffer.last_indentation_level(location(1, 6)),
+            Some(location(1, 4))
+        );
+        assert_eq!(buffer.last_indentation_level(location(0, 4)), None);
+        assert_eq!(buffer.last_indentation_level(location(2, 5)), None);
+        assert_eq!(buffer.last_indentation_level(location(3, 0)), None);
+
+        let buffer = Buffer::from_text("a.txt", "\t\t");
+        assert_eq!(
+            buffer.last_indentation_level(location(0, 2)),
+            Some(location(0, 1))
+        );
+    }
+
+    #[test]
+    fn edits_rehighlight_the_lines_below() {
+        let mut buffer = Buffer::from_text("a.rs", "let a;\n/* b\nc\nd */ fn");
+        let comments = |buffer: &mut Buffer| {
+            buffer.highlight(3);
+            (0..4)
+                .map(|line_idx| {
+                    buffer
+                        .get_highlights(line_idx)
+                        .iter()
+                        .filter(|annotation| annotation.kind == AnnotationType::Comment)
+                        .count()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(comments(&mut buffer), [0, 1, 1, 1]);
+        buffer.insert_str(" */", location(1, 4));
+        assert_eq!(comments(&mut buffer), [0, 1, 0, 0]);
+        buffer.delete_range(location(1, 0), location(1, 2));
+        assert_eq!(comments(&mut buffer), [0, 0, 0, 0]);
+        buffer.insert_str("/*", location(0, 0));
+        assert_eq!(comments(&mut buffer), [1, 1, 0, 0]);
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+
+use super::Location;
+
+// The number of changes kept for views which haven't caught up yet.
+const MAX_CHANGES: usize = 1000;
+
+// A change of the text, reduced to what's needed to keep locations pointing at the same text.
+#[derive(Copy, Clone)]
+pub enum TextChange {
+    Insert { at: Location, end: Location },
+    Remove { at: Location, end: Location },
+}
+
+impl TextChange {
+    // Moves a location along with the text around it. Locations at an insertion point end up behind
+    // the inserted text, locations within removed text at its start.
+    pub fn adjust(self, location: Location) -> Location {
+        match self {
+            Self::Insert { at, end } if location >= at => {
+                if location.line_idx == at.line_idx {
+                    Location {
+                        line_idx: end.line_idx,
+                        grapheme_idx: end
+                            .grapheme_idx
+                            .saturating_add(location.grapheme_idx.saturating_sub(at.grapheme_idx)),
+                    }
+                } else {
+                    Location {
+                        line_idx: location
+                            .line_idx
+                            .saturating_add(end.line_idx.saturating_sub(at.line_idx)),
+                        grapheme_idx: location.grapheme_idx,
+                    }
+                }
+            }
+            Self::Remove { at, end } if location > at => {
+                if location <= end {
+                    at
+                } else if location.line_idx == end.line_idx {
+                    Location {
+                        line_idx: at.line_idx,
+                        grapheme_idx: at
+                            .grapheme_idx
+                            .saturating_add(location.grapheme_idx.saturating_sub(end.grapheme_idx)),
+                    }
+                } else {
+                    Location {
+                        line_idx: location
+                            .line_idx
+                            .saturating_sub(end.line_idx.saturating_sub(at.line_idx)),
+                        grapheme_idx: location.grapheme_idx,
+                    }
+                }
+            }
+            Self::Insert { .. } | Self::Remove { .. } => location,
+        }
+    }
+}
+
+// The latest changes of a buffer's text, each with the revision it led to, so views which didn't make
+// a change can still keep their caret and selection on the same text.
+#[derive(Default)]
+pub struct ChangeLog {
+    changes: VecDeque<(usize, TextChange)>,
+    oldest_revision: usize, // the revision right before the oldest change kept
+}
+
+impl ChangeLog {
+    pub fn push(&mut self, revision: usize, change: TextChange) {
+        if self.changes.len() >= MAX_CHANGES {
+            if let Some((dropped_revision, _)) = self.changes.pop_front() {
+                self.oldest_revision = dropped_revision;
+            }
+        }
+        self.changes.push_back((revision, change));
+    }
+
+    // Returns the changes made after the given revision, or None if some of them are no longer kept.
+    pub fn since(&self, revision: usize) -> Option<impl Iterator<Item = TextChange> + '_> {
+        (revision >= self.oldest_revision).then(|| {
+            self.changes
+                .iter()
+                .filter(move |(change_revision, _)| *change_revision > revision)
+                .map(|(_, change)| *change)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn location(line_idx: usize, grapheme_idx: usize) -> Location {
+        Location {
+            line_idx,
+            grapheme_idx,
+        }
+    }
+
+    #[test]
+    fn insert_moves_locations_behind_it() {
+        // "ab\ncd" inserted at 1:2, ending at 2:2
+        let change = TextChange::Insert {
+            at: location(1, 2),
+            end: location(2, 2),
+        };
+        assert_eq!(change.adjust(location(0, 5)), location(0, 5));
+        assert_eq!(change.adjust(location(1, 1)), location(1, 1));
+        assert_eq!(change.adjust(location(1, 2)), location(2, 2));
+        assert_eq!(change.adjust(location(1, 4)), location(2, 4));
+        assert_eq!(change.adjust(location(3, 1)), location(4, 1));
+    }
+
+    #[test]
+    fn remove_moves_locations_behind_it() {
+        let change = TextChange::Remove {
+            at: location(1, 2),
+            end: location(3, 1),
+        };
+        assert_eq!(change.adjust(location(1, 2)), location(1, 2));
+        assert_eq!(change.adjust(location(2, 0)), location(1, 2));
+        assert_eq!(change.adjust(location(3, 1)), location(1, 2));
+        assert_eq!(change.adjust(location(3, 4)), location(1, 5));
+        assert_eq!(change.adjust(location(5, 3)), location(3, 3));
+    }
+
+    #[test]
+    fn forgets_the_oldest_changes() {
+        let change = TextChange::Insert {
+            at: location(0, 0),
+            end: location(1, 0),
+        };
+        let mut log = ChangeLog::default();
+        for revision in 1..=MAX_CHANGES.saturating_add(2) {
+            log.push(revision, change);
+        }
+        assert!(log.since(0).is_none());
+        assert!(log.since(1).is_none());
+        assert_eq!(log.since(2).map(Iterator::count), Some(MAX_CHANGES));
+        assert_eq!(log.since(MAX_CHANGES).map(Iterator::count), Some(2));
+    }
+}
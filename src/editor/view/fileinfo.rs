@@ -3,15 +3,28 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use super::{FileType, FileTypeSettings, Line};
+
+// Number of lines at the beginning and at the end of a file which are checked for modelines.
+const MODELINE_SEARCH_LINES: usize = 5;
+
 #[derive(Default, Debug)]
 pub struct FileInfo {
     path: Option<PathBuf>,
+    file_type: FileType,
 }
 
 impl FileInfo {
     pub fn from(file_name: &str) -> Self {
+        let path = PathBuf::from(file_name);
+        let file_type = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(FileType::from_extension)
+            .unwrap_or_default();
         Self {
-            path: Some(PathBuf::from(file_name)),
+            path: Some(path),
+            file_type,
         }
     }
     pub fn get_path(&self) -> Option<&Path> {
@@ -20,6 +33,128 @@ impl FileInfo {
     pub const fn has_path(&self) -> bool {
         self.path.is_some()
     }
+    pub const fn get_file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    // Refines the file type using the file contents: A modeline takes precedence over the extension,
+    // which in turn takes precedence over a shebang line.
+    pub fn detect_file_type(&mut self, lines: &[Line]) {
+        let from_extension = self
+            .get_path()
+            .and_then(Path::extension)
+            .and_then(|extension| extension.to_str())
+            .and_then(FileType::from_extension);
+        self.file_type = Self::modeline_candidates(lines)
+            .find_map(|line| Self::parse_modeline(line))
+            .or(from_extension)
+            .or_else(|| lines.first().and_then(|line| Self::parse_shebang(line)))
+            .unwrap_or_default();
+    }
+
+    // Overrides the indentation settings with the ones given in a modeline, like `vim: set et sw=2 ts=8:`
+    // or `-*- indent-tabs-mode: nil; tab-width: 8 -*-`.
+    pub fn apply_modeline_settings(lines: &[Line], settings: &mut FileTypeSettings) {
+        let width = |value: &str| value.trim().parse().ok().filter(|width| *width > 0);
+        for line in Self::modeline_candidates(lines) {
+            if let Some(options) = Self::vim_options(line) {
+                for option in options {
+                    let (key, value) = option.split_once('=').unwrap_or((option, ""));
+                    match key {
+                        "et" | "expandtab" => settings.use_tabs = false,
+                        "noet" | "noexpandtab" => settings.use_tabs = true,
+                        "sw" | "shiftwidth" | "sts" | "softtabstop" => {
+                            settings.indent_width = width(value).unwrap_or(settings.indent_width);
+                        }
+                        "ts" | "tabstop" => {
+                            settings.tab_width = width(value).unwrap_or(settings.tab_width);
+                        }
+                        _ => {}
+                    }
+                }
+            } else if let Some(options) = Self::emacs_options(line) {
+                for (key, value) in options.split(';').filter_map(|option| option.split_once(':')) {
+                    match key.trim() {
+                        "indent-tabs-mode" => settings.use_tabs = value.trim() != "nil",
+                        "tab-width" => settings.tab_width = width(value).unwrap_or(settings.tab_width),
+                        "c-basic-offset" | "python-indent-offset" | "rust-indent-offset" => {
+                            settings.indent_width = width(value).unwrap_or(settings.indent_width);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    // The lines which may contain a modeline.
+    fn modeline_candidates(lines: &[Line]) -> impl Iterator<Item = &Line> {
+        let head = lines.iter().take(MODELINE_SEARCH_LINES);
+        let tail = lines
+            .iter()
+            .skip(MODELINE_SEARCH_LINES.max(lines.len().saturating_sub(MODELINE_SEARCH_LINES)));
+        head.chain(tail)
+    }
+
+    // The options of a vim modeline, either flags like `et` or settings like `ft=python`.
+    // As in vim, the marker has to start the line or follow whitespace, so `index: 3` is no modeline.
+    fn vim_options(line: &str) -> Option<impl Iterator<Item = &str>> {
+        let start = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+            line.match_indices(marker)
+                .find(|(idx, _)| {
+                    line.get(..*idx).is_some_and(|before| {
+                        before.is_empty() || before.ends_with(char::is_whitespace)
+                    })
+                })
+                .map(|(idx, _)| idx.saturating_add(marker.len()))
+        })?;
+        let options = line.get(start..)?;
+        Some(
+            options
+                .split(|ch: char| ch == ':' || ch.is_whitespace())
+                .filter(|option| !option.is_empty()),
+        )
+    }
+
+    // The text between the markers of an emacs modeline.
+    fn emacs_options(line: &str) -> Option<&str> {
+        let start = line.find("-*-")?.saturating_add(3);
+        let options = line.get(start..)?;
+        Some(options.get(..options.find("-*-")?)?.trim())
+    }
+
+    // Understands vim modelines like `vim: set ft=python:` and emacs ones like `-*- mode: python -*-`.
+    fn parse_modeline(line: &str) -> Option<FileType> {
+        if let Some(mut options) = Self::vim_options(line) {
+            return options
+                .find_map(|option| {
+                    option
+                        .strip_prefix("ft=")
+                        .or_else(|| option.strip_prefix("filetype="))
+                })
+                .and_then(FileType::from_name);
+        }
+        let options = Self::emacs_options(line)?;
+        if !options.contains(':') {
+            return FileType::from_name(options);
+        }
+        options.split(';').find_map(|option| {
+            let (key, value) = option.split_once(':')?;
+            (key.trim().eq_ignore_ascii_case("mode"))
+                .then(|| FileType::from_name(value.trim()))
+                .flatten()
+        })
+    }
+
+    // Determines the file type from the interpreter in a line like `#!/usr/bin/env python3`.
+    fn parse_shebang(line: &str) -> Option<FileType> {
+        let mut words = line.strip_prefix("#!")?.split_whitespace();
+        let mut interpreter = words.next()?.rsplit('/').next()?;
+        if interpreter == "env" {
+            interpreter = words.find(|word| !word.starts_with('-'))?;
+        }
+        FileType::from_name(interpreter.trim_end_matches(|ch: char| ch.is_ascii_digit() || ch == '.'))
+    }
 }
 
 impl Display for FileInfo {
@@ -31,4 +166,126 @@ impl Display for FileInfo {
             .unwrap_or("[No Name]");
         write!(formatter, "{name}")
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(file_name: &str, text: &str) -> FileType {
+        let lines: Vec<Line> = text.lines().map(Line::from).collect();
+        let mut file_info = FileInfo::from(file_name);
+        file_info.detect_file_type(&lines);
+        file_info.get_file_type()
+    }
+
+    fn modeline_settings(text: &str) -> FileTypeSettings {
+        let lines: Vec<Line> = text.lines().map(Line::from).collect();
+        let mut settings = FileType::Rust.default_settings();
+        FileInfo::apply_modeline_settings(&lines, &mut settings);
+        settings
+    }
+
+    #[test]
+    fn vim_modelines() {
+        assert_eq!(
+            FileInfo::parse_modeline("# vim: set ft=python:"),
+            Some(FileType::Python)
+        );
+        assert_eq!(
+            FileInfo::parse_modeline("vim: filetype=rust"),
+            Some(FileType::Rust)
+        );
+        assert_eq!(FileInfo::parse_modeline("/* vi:ft=c */"), Some(FileType::C));
+        assert_eq!(
+            FileInfo::parse_modeline("\tex: ft=markdown"),
+            Some(FileType::Markdown)
+        );
+        assert_eq!(FileInfo::parse_modeline("# vim: set et sw=2:"), None);
+        assert_eq!(FileInfo::parse_modeline("# vim: ft=cobol"), None);
+    }
+
+    #[test]
+    fn vim_markers_need_whitespace_in_front() {
+        assert_eq!(FileInfo::parse_modeline("index: ft=python"), None);
+        assert_eq!(FileInfo::parse_modeline("navi: ft=python"), None);
+        assert_eq!(FileInfo::parse_modeline("text:vim: ft=python"), None);
+        assert_eq!(
+            modeline_settings("index: ts=2 sw=2"),
+            FileType::Rust.default_settings()
+        );
+    }
+
+    #[test]
+    fn emacs_modelines() {
+        assert_eq!(
+            FileInfo::parse_modeline("# -*- python -*-"),
+            Some(FileType::Python)
+        );
+        assert_eq!(
+            FileInfo::parse_modeline("// -*- mode: rust; tab-width: 8 -*-"),
+            Some(FileType::Rust)
+        );
+        assert_eq!(
+            FileInfo::parse_modeline("-*- Mode: C -*-"),
+            Some(FileType::C)
+        );
+        assert_eq!(FileInfo::parse_modeline("-*- coding: utf-8 -*-"), None);
+        assert_eq!(FileInfo::parse_modeline("-*- mode: rust"), None);
+    }
+
+    #[test]
+    fn modeline_indentation_settings() {
+        let settings = modeline_settings("// vim: set noet sw=2 ts=8:");
+        assert!(settings.use_tabs);
+        assert_eq!((settings.indent_width, settings.tab_width), (2, 8));
+        let settings = modeline_settings("x\n// -*- indent-tabs-mode: t; c-basic-offset: 3 -*-");
+        assert!(settings.use_tabs);
+        assert_eq!(settings.indent_width, 3);
+        assert_eq!(
+            modeline_settings("// vim: sw=0"),
+            FileType::Rust.default_settings()
+        );
+    }
+
+    #[test]
+    fn shebang_lines() {
+        assert_eq!(
+            FileInfo::parse_shebang("#!/usr/bin/env python3"),
+            Some(FileType::Python)
+        );
+        assert_eq!(
+            FileInfo::parse_shebang("#!/usr/bin/python3.12 -u"),
+            Some(FileType::Python)
+        );
+        assert_eq!(
+            FileInfo::parse_shebang("#!/usr/bin/env -S python -u"),
+            Some(FileType::Python)
+        );
+        assert_eq!(FileInfo::parse_shebang("#!/bin/sh"), None);
+        assert_eq!(FileInfo::parse_shebang("# python"), None);
+    }
+
+    #[test]
+    fn modeline_beats_extension_beats_shebang() {
+        let shebang = "#!/usr/bin/env python3\n";
+        let modeline = "\n# vim: ft=markdown\n";
+        assert_eq!(detect("script", shebang), FileType::Python);
+        assert_eq!(detect("script.rs", shebang), FileType::Rust);
+        assert_eq!(
+            detect("script.rs", &format!("{shebang}{modeline}")),
+            FileType::Markdown
+        );
+        assert_eq!(detect("script", "plain text"), FileType::Text);
+    }
+
+    #[test]
+    fn modelines_are_only_searched_at_the_start_and_end() {
+        let mut text = "x\n".repeat(5);
+        text.push_str("# vim: ft=python\n");
+        text.push_str(&"x\n".repeat(5));
+        assert_eq!(detect("notes", &text), FileType::Text);
+        text.push_str("# vim: ft=c\n");
+        assert_eq!(detect("notes", &text), FileType::C);
+    }
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+
+// Where to move the caret, as entered into the go to prompt. Lines and columns count from 1.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum GoToTarget {
+    Line { line: usize, col: Option<usize> },
+    Forward(usize),  // lines below the caret line
+    Backward(usize), // lines above the caret line
+    Percentage(usize),
+}
+
+impl TryFrom<&str> for GoToTarget {
+    type Error = String;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        let input = input.trim();
+        let parse = |number: &str| {
+            number
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid location: {input}"))
+        };
+        if let Some(lines) = input.strip_prefix('+') {
+            Ok(Self::Forward(parse(lines)?))
+        } else if let Some(lines) = input.strip_prefix('-') {
+            Ok(Self::Backward(parse(lines)?))
+        } else if let Some(percentage) = input.strip_suffix('%') {
+            Ok(Self::Percentage(parse(percentage)?))
+        } else if let Some((line, col)) = input.split_once(':') {
+            Ok(Self::Line {
+                line: parse(line)?,
+                col: Some(parse(col)?),
+            })
+        } else {
+            Ok(Self::Line {
+                line: parse(input)?,
+                col: None,
+            })
+        }
+    }
+}
+
+impl fmt::Display for GoToTarget {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Line { line, col: None } => write!(formatter, "{line}"),
+            Self::Line {
+                line,
+                col: Some(col),
+            } => write!(formatter, "{line}:{col}"),
+            Self::Forward(lines) => write!(formatter, "+{lines}"),
+            Self::Backward(lines) => write!(formatter, "-{lines}"),
+            Self::Percentage(percentage) => write!(formatter, "{percentage}%"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<GoToTarget, String> {
+        GoToTarget::try_from(input)
+    }
+
+    #[test]
+    fn lines_and_columns() {
+        assert_eq!(
+            parse("42"),
+            Ok(GoToTarget::Line {
+                line: 42,
+                col: None
+            })
+        );
+        assert_eq!(
+            parse(" 42:7 "),
+            Ok(GoToTarget::Line {
+                line: 42,
+                col: Some(7)
+            })
+        );
+        assert_eq!(parse("0"), Ok(GoToTarget::Line { line: 0, col: None }));
+    }
+
+    #[test]
+    fn relative_lines_and_percentages() {
+        assert_eq!(parse("+5"), Ok(GoToTarget::Forward(5)));
+        assert_eq!(parse("-5"), Ok(GoToTarget::Backward(5)));
+        assert_eq!(parse("50%"), Ok(GoToTarget::Percentage(50)));
+        assert_eq!(parse("250%"), Ok(GoToTarget::Percentage(250)));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        for input in [
+            "",
+            "abc",
+            "4x",
+            "+",
+            "-",
+            "%",
+            "4:",
+            ":7",
+            "1:2:3",
+            "+-3",
+            "5%%",
+            "99999999999999999999999",
+        ] {
+            assert_eq!(
+                parse(input),
+                Err(format!("Invalid location: {}", input.trim()))
+            );
+        }
+    }
+
+    #[test]
+    fn displays_as_entered() {
+        for input in ["42", "42:7", "+5", "-5", "50%"] {
+            assert_eq!(parse(input).unwrap().to_string(), input);
+        }
+    }
+}
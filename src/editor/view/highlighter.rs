@@ -0,0 +1,140 @@
+use super::{Annotation, AnnotationType, FileType, Line};
+mod code;
+mod markdown;
+mod syntax;
+pub use syntax::Syntax;
+use syntax::{CodeRules, Rules};
+
+// What is still open at the end of a line and needs to be continued on the next one.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug)]
+pub enum State {
+    #[default]
+    Normal,
+    BlockComment,
+    String(usize), // index of the delimiter in CodeRules::strings
+    CodeBlock,
+}
+
+struct LineHighlight {
+    annotations: Vec<Annotation>,
+    end_state: State,
+}
+
+// Assigns token types to the lines of a buffer according to a syntax.
+// Results are cached per line. Since a line's highlighting only depends on the lines above it,
+// an edit only invalidates the edited line and the ones below, and these are recomputed lazily
+// when they are about to be drawn.
+#[derive(Default)]
+pub struct Highlighter {
+    syntax: Option<&'static Syntax>,
+    line_comment: Option<String>,
+    highlights: Vec<LineHighlight>,
+}
+
+impl Highlighter {
+    pub const fn new(syntax: Option<&'static Syntax>, line_comment: Option<String>) -> Self {
+        Self {
+            syntax,
+            line_comment,
+            highlights: Vec::new(),
+        }
+    }
+
+    pub fn invalidate_from(&mut self, line_idx: usize) {
+        self.highlights.truncate(line_idx);
+    }
+
+    // Makes sure all lines up to and including until_line_idx are highlighted.
+    pub fn update(&mut self, lines: &[Line], until_line_idx: usize) {
+        let Some(syntax) = self.syntax else {
+            return;
+        };
+        let start = self.highlights.len();
+        for line in lines.iter().take(until_line_idx.saturating_add(1)).skip(start) {
+            let state = self
+                .highlights
+                .last()
+                .map_or(State::default(), |highlight| highlight.end_state);
+            let (annotations, end_state) = match &syntax.rules {
+                Rules::Code(rules) => {
+                    code::highlight_line(rules, self.line_comment.as_deref(), line, state)
+                }
+                Rules::Markdown => markdown::highlight_line(line, state),
+            };
+            self.highlights.push(LineHighlight {
+                annotations,
+                end_state,
+            });
+        }
+    }
+
+    pub fn get_annotations(&self, line_idx: usize) -> &[Annotation] {
+        self.highlights
+            .get(line_idx)
+            .map_or(&[], |highlight| &highlight.annotations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlighter(extension: &str) -> Highlighter {
+        Highlighter::new(Syntax::for_extension(extension), Some(String::from("//")))
+    }
+
+    fn lines(text: &str) -> Vec<Line> {
+        text.split('\n').map(Line::from).collect()
+    }
+
+    fn kinds(highlighter: &Highlighter, line_idx: usize) -> Vec<AnnotationType> {
+        highlighter
+            .get_annotations(line_idx)
+            .iter()
+            .map(|annotation| annotation.kind)
+            .collect()
+    }
+
+    #[test]
+    fn highlights_lazily_up_to_the_given_line() {
+        let mut highlighter = highlighter("rs");
+        let lines = lines("/* a\nb\nc */ fn");
+        highlighter.update(&lines, 1);
+        assert_eq!(highlighter.highlights.len(), 2);
+        assert_eq!(kinds(&highlighter, 1), [AnnotationType::Comment]);
+        assert!(kinds(&highlighter, 2).is_empty());
+        highlighter.update(&lines, 10);
+        assert_eq!(
+            kinds(&highlighter, 2),
+            [AnnotationType::Comment, AnnotationType::Keyword]
+        );
+    }
+
+    #[test]
+    fn rehighlights_from_the_invalidated_line() {
+        let mut highlighter = highlighter("rs");
+        let mut lines = lines("/* a\nb\nc */ fn");
+        highlighter.update(&lines, 2);
+        lines[0] = Line::from("let a");
+        highlighter.invalidate_from(0);
+        assert!(highlighter.highlights.is_empty());
+        highlighter.update(&lines, 2);
+        assert_eq!(kinds(&highlighter, 0), [AnnotationType::Keyword]);
+        assert!(kinds(&highlighter, 1).is_empty());
+        assert_eq!(kinds(&highlighter, 2), [AnnotationType::Keyword]);
+
+        // Lines above the invalidated one keep their cached highlighting
+        lines[2] = Line::from("/* c");
+        highlighter.invalidate_from(2);
+        assert_eq!(highlighter.highlights.len(), 2);
+        highlighter.update(&lines, 2);
+        assert_eq!(kinds(&highlighter, 2), [AnnotationType::Comment]);
+    }
+
+    #[test]
+    fn plain_text_is_not_highlighted() {
+        let mut highlighter = Highlighter::new(Syntax::for_file_type(FileType::Text), None);
+        highlighter.update(&lines("fn main() {}"), 0);
+        assert!(highlighter.get_annotations(0).is_empty());
+    }
+}
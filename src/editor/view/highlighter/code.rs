@@ -0,0 +1,371 @@
+use super::{Annotation, AnnotationType, CodeRules, State};
+
+// Highlights a line of source code, continuing constructs left open by the previous line as given by `state`.
+// The line comment token is passed separately, since it can be configured per file type.
+// Returns the annotations for the line together with the state at its end.
+pub fn highlight_line(
+    rules: &CodeRules,
+    line_comment: Option<&str>,
+    line: &str,
+    state: State,
+) -> (Vec<Annotation>, State) {
+    let mut annotations = Vec::new();
+    let Some(start) = resume(rules, line, state, &mut annotations) else {
+        return (annotations, state);
+    };
+    let end_state = highlight_from(rules, line_comment, line, start, &mut annotations);
+    (annotations, end_state)
+}
+
+fn annotate(annotations: &mut Vec<Annotation>, kind: AnnotationType, start: usize, end: usize) {
+    annotations.push(Annotation { kind, start, end });
+}
+
+// Finishes what the previous line left open, as well as directives at the start of the line.
+// Returns the byte index at which regular highlighting continues, or None if the construct spans the whole line.
+fn resume(
+    rules: &CodeRules,
+    line: &str,
+    state: State,
+    annotations: &mut Vec<Annotation>,
+) -> Option<usize> {
+    let (kind, closing) = match state {
+        State::BlockComment => (
+            AnnotationType::Comment,
+            find_closing(line, 0, rules.block_comment.map_or("", |(_, close)| close), false),
+        ),
+        State::String(delimiter_idx) => (
+            AnnotationType::String,
+            find_closing(
+                line,
+                0,
+                rules
+                    .strings
+                    .get(delimiter_idx)
+                    .map_or("", |(delimiter, _)| delimiter),
+                true,
+            ),
+        ),
+        State::Normal | State::CodeBlock => {
+            let start = line.len().saturating_sub(line.trim_start().len());
+            return Some(
+                rules
+                    .directive_prefix
+                    .filter(|&prefix| line.get(start..).is_some_and(|rest| rest.starts_with(prefix)))
+                    .map_or(0, |prefix| {
+                        let end = word_end(line, start.saturating_add(prefix.len_utf8()));
+                        annotate(annotations, AnnotationType::Keyword, start, end);
+                        end
+                    }),
+            );
+        }
+    };
+    let end = closing.unwrap_or(line.len());
+    annotate(annotations, kind, 0, end);
+    closing
+}
+
+// Highlights the line from the given byte index onward and returns the state at the end of the line.
+fn highlight_from(
+    rules: &CodeRules,
+    line_comment: Option<&str>,
+    line: &str,
+    start: usize,
+    annotations: &mut Vec<Annotation>,
+) -> State {
+    let mut annotate = |kind, start, end| annotate(annotations, kind, start, end);
+    let mut idx = start;
+    while let Some(rest) = line.get(idx..).filter(|rest| !rest.is_empty()) {
+        let Some(ch) = rest.chars().next() else {
+            break;
+        };
+        let at_word_start = !line
+            .get(..idx)
+            .and_then(|before| before.chars().next_back())
+            .is_some_and(is_word_char);
+
+        if line_comment.is_some_and(|start| rest.starts_with(start)) {
+            annotate(AnnotationType::Comment, idx, line.len());
+            break;
+        }
+        if let Some((open, close)) = rules
+            .block_comment
+            .filter(|(open, _)| rest.starts_with(open))
+        {
+            if let Some(end) = find_closing(line, idx.saturating_add(open.len()), close, false) {
+                annotate(AnnotationType::Comment, idx, end);
+                idx = end;
+                continue;
+            }
+            annotate(AnnotationType::Comment, idx, line.len());
+            return State::BlockComment;
+        }
+        if let Some((delimiter_idx, (delimiter, multiline))) = rules
+            .strings
+            .iter()
+            .enumerate()
+            .find(|(_, (delimiter, _))| rest.starts_with(delimiter))
+        {
+            if let Some(end) = find_closing(line, idx.saturating_add(delimiter.len()), delimiter, true)
+            {
+                annotate(AnnotationType::String, idx, end);
+                idx = end;
+                continue;
+            }
+            annotate(AnnotationType::String, idx, line.len());
+            return if *multiline {
+                State::String(delimiter_idx)
+            } else {
+                State::Normal
+            };
+        }
+        if rules.char_literals && ch == '\'' {
+            if let Some(end) = char_literal_end(line, idx) {
+                annotate(AnnotationType::String, idx, end);
+                idx = end;
+                continue;
+            }
+        }
+        if at_word_start && ch.is_ascii_digit() {
+            let end = number_end(line, idx);
+            annotate(AnnotationType::Number, idx, end);
+            idx = end;
+            continue;
+        }
+        if at_word_start && is_word_char(ch) {
+            let end = word_end(line, idx);
+            let word = line.get(idx..end).unwrap_or_default();
+            if rules.keywords.contains(&word) {
+                annotate(AnnotationType::Keyword, idx, end);
+            } else if rules.types.contains(&word) {
+                annotate(AnnotationType::Type, idx, end);
+            }
+            idx = end;
+            continue;
+        }
+        idx = idx.saturating_add(ch.len_utf8());
+    }
+    State::Normal
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+// Returns the byte index behind the word starting at `from`.
+fn word_end(line: &str, from: usize) -> usize {
+    line.get(from..)
+        .and_then(|rest| rest.find(|ch| !is_word_char(ch)))
+        .map_or(line.len(), |len| from.saturating_add(len))
+}
+
+// Returns the byte index behind the number starting at `from`, which may contain a decimal point,
+// a prefix like 0x or a suffix like u32.
+fn number_end(line: &str, from: usize) -> usize {
+    let mut end = from;
+    let mut chars = line.get(from..).unwrap_or_default().chars().peekable();
+    while let Some(ch) = chars.next() {
+        let is_decimal_point = ch == '.' && chars.peek().is_some_and(char::is_ascii_digit);
+        if !(is_word_char(ch) || is_decimal_point) {
+            break;
+        }
+        end = end.saturating_add(ch.len_utf8());
+    }
+    end
+}
+
+// Returns the byte index behind the first occurrence of `delimiter` at or after `from`,
+// skipping characters escaped with a backslash if requested.
+fn find_closing(line: &str, from: usize, delimiter: &str, escapes: bool) -> Option<usize> {
+    if delimiter.is_empty() {
+        return None;
+    }
+    let mut chars = line.get(from..)?.char_indices();
+    while let Some((offset, ch)) = chars.next() {
+        if escapes && ch == '\\' {
+            chars.next();
+            continue;
+        }
+        let idx = from.saturating_add(offset);
+        if line.get(idx..).is_some_and(|rest| rest.starts_with(delimiter)) {
+            return Some(idx.saturating_add(delimiter.len()));
+        }
+    }
+    None
+}
+
+// Single quotes are also used for lifetimes and labels in Rust. They only start a character literal
+// if they enclose a single character or an escape sequence.
+fn char_literal_end(line: &str, from: usize) -> Option<usize> {
+    const MAX_ESCAPE_LEN: usize = 10; // long enough for '\u{10FFFF}'
+    let content_start = from.saturating_add(1);
+    let mut chars = line.get(content_start..)?.chars();
+    match chars.next()? {
+        '\\' => find_closing(line, content_start, "'", true)
+            .filter(|end| end.saturating_sub(from) <= MAX_ESCAPE_LEN.saturating_add(2)),
+        '\'' => None,
+        ch => (chars.next()? == '\'')
+            .then(|| content_start.saturating_add(ch.len_utf8()).saturating_add(1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Rules, Syntax};
+    use super::*;
+
+    fn rules(extension: &str) -> &'static CodeRules {
+        match Syntax::for_extension(extension).map(|syntax| &syntax.rules) {
+            Some(Rules::Code(rules)) => rules,
+            _ => panic!("no code rules for {extension}"),
+        }
+    }
+
+    // Highlights the given lines one after the other, returning the highlighted text and the final state.
+    fn tokens(
+        extension: &str,
+        line_comment: Option<&str>,
+        lines: &[&'static str],
+    ) -> (Vec<(AnnotationType, &'static str)>, State) {
+        let mut state = State::default();
+        let mut tokens = Vec::new();
+        for line in lines {
+            let (annotations, end_state) =
+                highlight_line(rules(extension), line_comment, line, state);
+            tokens.extend(annotations.iter().map(|annotation| {
+                (
+                    annotation.kind,
+                    line.get(annotation.start..annotation.end)
+                        .unwrap_or_default(),
+                )
+            }));
+            state = end_state;
+        }
+        (tokens, state)
+    }
+
+    #[test]
+    fn words_numbers_and_comments() {
+        assert_eq!(
+            tokens("rs", Some("//"), &["let x: u32 = 0x1F + 1.5 * x2; // done"]),
+            (
+                vec![
+                    (AnnotationType::Keyword, "let"),
+                    (AnnotationType::Type, "u32"),
+                    (AnnotationType::Number, "0x1F"),
+                    (AnnotationType::Number, "1.5"),
+                    (AnnotationType::Comment, "// done"),
+                ],
+                State::Normal
+            )
+        );
+        // The line comment token is configurable
+        assert_eq!(
+            tokens("py", None, &["pass # no comment"]),
+            (vec![(AnnotationType::Keyword, "pass")], State::Normal)
+        );
+        assert_eq!(
+            tokens("c", Some("//"), &["  #include <stdio.h>"]),
+            (vec![(AnnotationType::Keyword, "#include")], State::Normal)
+        );
+    }
+
+    #[test]
+    fn block_comments_continue_on_the_next_lines() {
+        assert_eq!(
+            tokens("rs", Some("//"), &["fn /* open", "still open"]),
+            (
+                vec![
+                    (AnnotationType::Keyword, "fn"),
+                    (AnnotationType::Comment, "/* open"),
+                    (AnnotationType::Comment, "still open"),
+                ],
+                State::BlockComment
+            )
+        );
+        assert_eq!(
+            tokens("c", Some("//"), &["/* open", "closed */ int /* x */ 1"]),
+            (
+                vec![
+                    (AnnotationType::Comment, "/* open"),
+                    (AnnotationType::Comment, "closed */"),
+                    (AnnotationType::Type, "int"),
+                    (AnnotationType::Comment, "/* x */"),
+                    (AnnotationType::Number, "1"),
+                ],
+                State::Normal
+            )
+        );
+    }
+
+    #[test]
+    fn multiline_strings_continue_on_the_next_lines() {
+        assert_eq!(
+            tokens(
+                "py",
+                Some("#"),
+                &[r#"x = """doc"#, r#"# more ""#, r#"end""" if"#]
+            ),
+            (
+                vec![
+                    (AnnotationType::String, r#""""doc"#),
+                    (AnnotationType::String, r#"# more ""#),
+                    (AnnotationType::String, r#"end""""#),
+                    (AnnotationType::Keyword, "if"),
+                ],
+                State::Normal
+            )
+        );
+        assert_eq!(tokens("py", Some("#"), &["'''open"]).1, State::String(1));
+        assert_eq!(
+            tokens("rs", Some("//"), &[r#"let s = "a\"b"#]),
+            (
+                vec![
+                    (AnnotationType::Keyword, "let"),
+                    (AnnotationType::String, r#""a\"b"#),
+                ],
+                State::String(0)
+            )
+        );
+        // Single-line strings end with their line
+        assert_eq!(
+            tokens("c", Some("//"), &["\"open", "int"]),
+            (
+                vec![
+                    (AnnotationType::String, "\"open"),
+                    (AnnotationType::Type, "int"),
+                ],
+                State::Normal
+            )
+        );
+    }
+
+    #[test]
+    fn char_literals_and_lifetimes() {
+        assert_eq!(
+            tokens("rs", Some("//"), &["fn f<'a>(s: &'a str) -> char { 'x' }"]).0,
+            [
+                (AnnotationType::Keyword, "fn"),
+                (AnnotationType::Type, "str"),
+                (AnnotationType::Type, "char"),
+                (AnnotationType::String, "'x'"),
+            ]
+        );
+        assert_eq!(
+            tokens("rs", Some("//"), &[r"['\n', '\'', '\u{1F980}', 'ü']"]).0,
+            [
+                (AnnotationType::String, r"'\n'"),
+                (AnnotationType::String, r"'\''"),
+                (AnnotationType::String, r"'\u{1F980}'"),
+                (AnnotationType::String, "'ü'"),
+            ]
+        );
+        assert_eq!(
+            tokens("rs", Some("//"), &["'outer: loop { break 'outer; }"]).0,
+            [
+                (AnnotationType::Keyword, "loop"),
+                (AnnotationType::Keyword, "break"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use super::{Annotation, AnnotationType, State};
+
+// Highlights a line of Markdown. The state tracks whether we're inside a fenced code block.
+pub fn highlight_line(line: &str, state: State) -> (Vec<Annotation>, State) {
+    let whole_line = |kind| {
+        vec![Annotation {
+            kind,
+            start: 0,
+            end: line.len(),
+        }]
+    };
+    let trimmed = line.trim_start();
+    let indent = line.len().saturating_sub(trimmed.len());
+
+    if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+        let state = if state == State::CodeBlock {
+            State::Normal
+        } else {
+            State::CodeBlock
+        };
+        return (whole_line(AnnotationType::Code), state);
+    }
+    if state == State::CodeBlock {
+        return (whole_line(AnnotationType::Code), state);
+    }
+    if trimmed.starts_with('#') {
+        return (whole_line(AnnotationType::Heading), State::Normal);
+    }
+    if trimmed.starts_with('>') {
+        return (whole_line(AnnotationType::Comment), State::Normal);
+    }
+
+    let mut annotations = Vec::new();
+    let mut idx = indent;
+    if let Some(marker_len) = list_marker_len(trimmed) {
+        let end = indent.saturating_add(marker_len);
+        annotations.push(Annotation {
+            kind: AnnotationType::Keyword,
+            start: indent,
+            end,
+        });
+        idx = end;
+    }
+    while let Some(rest) = line.get(idx..).filter(|rest| !rest.is_empty()) {
+        let delimiter = ["`", "**", "__", "*", "_"]
+            .into_iter()
+            .find(|delimiter| rest.starts_with(delimiter));
+        if let Some(delimiter) = delimiter {
+            let content_start = idx.saturating_add(delimiter.len());
+            if let Some(end) = line
+                .get(content_start..)
+                .and_then(|content| content.find(delimiter))
+                .filter(|&len| len > 0)
+                .map(|len| content_start.saturating_add(len).saturating_add(delimiter.len()))
+            {
+                let kind = if delimiter == "`" {
+                    AnnotationType::Code
+                } else {
+                    AnnotationType::Emphasis
+                };
+                annotations.push(Annotation {
+                    kind,
+                    start: idx,
+                    end,
+                });
+                idx = end;
+                continue;
+            }
+        }
+        idx = idx.saturating_add(rest.chars().next().map_or(1, char::len_utf8));
+    }
+    (annotations, State::Normal)
+}
+
+// Returns the length of a list marker like "- ", "* " or "1. " at the start of the given text.
+fn list_marker_len(text: &str) -> Option<usize> {
+    if ["- ", "* ", "+ "].iter().any(|marker| text.starts_with(marker)) {
+        return Some(2);
+    }
+    let digits = text.len().saturating_sub(text.trim_start_matches(|ch: char| ch.is_ascii_digit()).len());
+    (digits > 0
+        && text
+            .get(digits..)
+            .is_some_and(|rest| rest.starts_with(". ") || rest.starts_with(") ")))
+    .then(|| digits.saturating_add(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(lines: &[&'static str]) -> (Vec<(AnnotationType, &'static str)>, State) {
+        let mut state = State::default();
+        let mut tokens = Vec::new();
+        for line in lines {
+            let (annotations, end_state) = highlight_line(line, state);
+            tokens.extend(annotations.iter().map(|annotation| {
+                (
+                    annotation.kind,
+                    line.get(annotation.start..annotation.end)
+                        .unwrap_or_default(),
+                )
+            }));
+            state = end_state;
+        }
+        (tokens, state)
+    }
+
+    #[test]
+    fn headings_quotes_and_lists() {
+        assert_eq!(
+            tokens(&["# Title", "> quote", "- item", "  12. item", "-not a list"]).0,
+            [
+                (AnnotationType::Heading, "# Title"),
+                (AnnotationType::Comment, "> quote"),
+                (AnnotationType::Keyword, "- "),
+                (AnnotationType::Keyword, "12. "),
+            ]
+        );
+    }
+
+    #[test]
+    fn inline_code_and_emphasis() {
+        assert_eq!(
+            tokens(&["a `b*c*` **d** _e_ 2 * 3"]).0,
+            [
+                (AnnotationType::Code, "`b*c*`"),
+                (AnnotationType::Emphasis, "**d**"),
+                (AnnotationType::Emphasis, "_e_"),
+            ]
+        );
+    }
+
+    #[test]
+    fn fenced_code_blocks_span_lines() {
+        assert_eq!(
+            tokens(&["```rust", "# not a heading", "- no list"]),
+            (
+                vec![
+                    (AnnotationType::Code, "```rust"),
+                    (AnnotationType::Code, "# not a heading"),
+                    (AnnotationType::Code, "- no list"),
+                ],
+                State::CodeBlock
+            )
+        );
+        assert_eq!(
+            tokens(&["~~~", "x", "~~~", "# heading"]),
+            (
+                vec![
+                    (AnnotationType::Code, "~~~"),
+                    (AnnotationType::Code, "x"),
+                    (AnnotationType::Code, "~~~"),
+                    (AnnotationType::Heading, "# heading"),
+                ],
+                State::Normal
+            )
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use super::FileType;
+
+// Describes how the text of a language is highlighted.
+pub struct Syntax {
+    pub extensions: &'static [&'static str],
+    pub rules: Rules,
+}
+
+pub enum Rules {
+    Code(CodeRules),
+    Markdown,
+}
+
+// Rules for programming languages: block comments, strings, numbers and words of special meaning.
+pub struct CodeRules {
+    pub keywords: &'static [&'static str],
+    pub types: &'static [&'static str],
+    pub block_comment: Option<(&'static str, &'static str)>,
+    // String delimiters, each with a flag telling whether strings may span multiple lines.
+    // Longer delimiters sharing a prefix with shorter ones need to come first.
+    pub strings: &'static [(&'static str, bool)],
+    // Whether single quotes denote character literals like 'a' or '\n', without clashing with other uses of single quotes.
+    pub char_literals: bool,
+    // Character introducing preprocessor directives at the start of a line.
+    pub directive_prefix: Option<char>,
+}
+
+pub const SYNTAXES: &[Syntax] = &[RUST, C, PYTHON, MARKDOWN];
+
+impl Syntax {
+    pub fn for_extension(extension: &str) -> Option<&'static Self> {
+        SYNTAXES
+            .iter()
+            .find(|syntax| syntax.extensions.contains(&extension))
+    }
+    // The file type may also come from a shebang line or modeline, so it's looked up by its extensions.
+    pub fn for_file_type(file_type: FileType) -> Option<&'static Self> {
+        file_type
+            .extensions()
+            .iter()
+            .find_map(|extension| Self::for_extension(extension))
+    }
+}
+
+const RUST: Syntax = Syntax {
+    extensions: &["rs"],
+    rules: Rules::Code(CodeRules {
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        types: &[
+            "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+            "i64", "i128", "isize", "f32", "f64", "String", "Vec", "Option", "Result", "Box",
+            "Some", "None", "Ok", "Err",
+        ],
+        block_comment: Some(("/*", "*/")),
+        strings: &[("\"", true)],
+        char_literals: true,
+        directive_prefix: None,
+    }),
+};
+
+const C: Syntax = Syntax {
+    extensions: &["c", "h"],
+    rules: Rules::Code(CodeRules {
+        keywords: &[
+            "auto", "break", "case", "const", "continue", "default", "do", "else", "enum",
+            "extern", "for", "goto", "if", "inline", "register", "restrict", "return", "sizeof",
+            "static", "struct", "switch", "typedef", "union", "volatile", "while", "NULL", "true",
+            "false",
+        ],
+        types: &[
+            "char", "short", "int", "long", "float", "double", "void", "signed", "unsigned",
+            "bool", "_Bool", "size_t", "int8_t", "int16_t", "int32_t", "int64_t", "uint8_t",
+            "uint16_t", "uint32_t", "uint64_t",
+        ],
+        block_comment: Some(("/*", "*/")),
+        strings: &[("\"", false), ("'", false)],
+        char_literals: false,
+        directive_prefix: Some('#'),
+    }),
+};
+
+const PYTHON: Syntax = Syntax {
+    extensions: &["py", "pyw"],
+    rules: Rules::Code(CodeRules {
+        keywords: &[
+            "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+            "continue", "def", "del", "elif", "else", "except", "finally", "for", "from",
+            "global", "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass",
+            "raise", "return", "try", "while", "with", "yield", "self",
+        ],
+        types: &[
+            "int", "float", "complex", "str", "bytes", "bool", "list", "tuple", "dict", "set",
+            "frozenset", "object",
+        ],
+        block_comment: None,
+        strings: &[("\"\"\"", true), ("'''", true), ("\"", false), ("'", false)],
+        char_literals: false,
+        directive_prefix: None,
+    }),
+};
+
+const MARKDOWN: Syntax = Syntax {
+    extensions: &["md", "markdown"],
+    rules: Rules::Markdown,
+};
@@ -0,0 +1,253 @@
+use super::{Location, Position};
+
+// The caret and scroll state of the view right before a step was taken, so undo can put the user back where they were.
+#[derive(Copy, Clone, Default)]
+pub struct Snapshot {
+    pub location: Location,
+    pub scroll_offset: Position,
+}
+
+// A single modification of the buffer. Each variant carries enough information to be applied and reverted.
+pub enum Change {
+    Insert {
+        at: Location,
+        end: Location,
+        text: String,
+    },
+    Remove {
+        at: Location,
+        end: Location,
+        text: String,
+    },
+    PushLine {
+        line_idx: usize,
+    },
+}
+
+impl Change {
+    // Returns where the change begins.
+    pub const fn start(&self) -> Location {
+        match self {
+            Self::Insert { at, .. } | Self::Remove { at, .. } => *at,
+            Self::PushLine { line_idx } => Location {
+                line_idx: *line_idx,
+                grapheme_idx: 0,
+            },
+        }
+    }
+    // Returns where the caret should be placed after this change has been (re)applied.
+    pub const fn caret_after(&self) -> Location {
+        match self {
+            Self::Insert { end, .. } => *end,
+            Self::Remove { at, .. } => *at,
+            Self::PushLine { line_idx } => Location {
+                line_idx: line_idx.saturating_add(1),
+                grapheme_idx: 0,
+            },
+        }
+    }
+}
+
+// One undoable unit, consisting of one or more changes.
+pub struct Step {
+    id: usize,
+    pub changes: Vec<Change>,
+    pub before: Snapshot,
+    coalesce: bool,
+}
+
+#[derive(Default)]
+pub struct History {
+    undo_stack: Vec<Step>,
+    redo_stack: Vec<Step>,
+    pending: Option<(Snapshot, bool)>,
+    open: bool, // whether further changes belong to the last step, until it's undone, redone or saved
+    sealed: bool,
+    next_id: usize,
+    saved_id: Option<usize>,
+}
+
+impl History {
+    // Announces a new step. The step is only created once the first change is recorded,
+    // so commands which end up not modifying anything don't leave empty steps behind.
+    // If `coalesce` is true, the step may be merged into the previous one, provided that one was
+    // also coalescing and the new change continues right where the previous one ended.
+    pub fn begin_step(&mut self, before: Snapshot, coalesce: bool) {
+        self.pending = Some((before, coalesce));
+        self.open = false;
+    }
+
+    // Records a change into the current step. A new step is started if one has been announced,
+    // or if the last step is no longer open, so every change can be undone and marks the buffer modified.
+    pub fn record(&mut self, change: Change) {
+        self.redo_stack.clear();
+        if self.pending.is_none() && self.open {
+            if let Some(step) = self.undo_stack.last_mut() {
+                step.changes.push(change);
+                return;
+            }
+        }
+        let (before, coalesce) = self.pending.take().unwrap_or_else(|| {
+            let before = Snapshot {
+                location: change.start(),
+                ..Snapshot::default()
+            };
+            (before, false)
+        });
+        self.open = true;
+        if coalesce && self.try_coalesce(&change) {
+            return;
+        }
+        self.undo_stack.push(Step {
+            id: self.next_id,
+            changes: vec![change],
+            before,
+            coalesce,
+        });
+        self.next_id = self.next_id.saturating_add(1);
+        self.sealed = false;
+    }
+
+    fn try_coalesce(&mut self, change: &Change) -> bool {
+        if self.sealed {
+            return false;
+        }
+        let saved_id = self.saved_id;
+        let Some(step) = self
+            .undo_stack
+            .last_mut()
+            .filter(|step| step.coalesce && Some(step.id) != saved_id)
+        else {
+            return false;
+        };
+        if let (
+            Some(Change::Insert {
+                end: prev_end,
+                text: prev_text,
+                ..
+            }),
+            Change::Insert { at, end, text },
+        ) = (step.changes.last_mut(), change)
+        {
+            if prev_end == at && !text.contains('\n') {
+                *prev_end = *end;
+                prev_text.push_str(text);
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Step> {
+        self.pending = None;
+        self.open = false;
+        self.sealed = true;
+        self.undo_stack.pop()
+    }
+    pub fn push_undo(&mut self, step: Step) {
+        self.undo_stack.push(step);
+    }
+    pub fn pop_redo(&mut self) -> Option<Step> {
+        self.pending = None;
+        self.open = false;
+        self.sealed = true;
+        self.redo_stack.pop()
+    }
+    pub fn push_redo(&mut self, step: Step) {
+        self.redo_stack.push(step);
+    }
+
+    // Remembers the current state as the one which is stored on disk.
+    pub fn mark_saved(&mut self) {
+        self.open = false;
+        self.saved_id = self.undo_stack.last().map(|step| step.id);
+    }
+    pub fn is_modified(&self) -> bool {
+        self.undo_stack.last().map(|step| step.id) != self.saved_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(grapheme_idx: usize, text: &str) -> Change {
+        let at = Location {
+            line_idx: 0,
+            grapheme_idx,
+        };
+        Change::Insert {
+            at,
+            end: Location {
+                line_idx: 0,
+                grapheme_idx: grapheme_idx.saturating_add(text.len()),
+            },
+            text: text.to_string(),
+        }
+    }
+
+    fn step_sizes(history: &History) -> Vec<usize> {
+        history
+            .undo_stack
+            .iter()
+            .map(|step| step.changes.len())
+            .collect()
+    }
+
+    #[test]
+    fn announced_steps_take_all_following_changes() {
+        let mut history = History::default();
+        history.begin_step(Snapshot::default(), false);
+        history.record(insert(0, "a"));
+        history.record(insert(1, "b"));
+        history.begin_step(Snapshot::default(), false);
+        history.record(insert(2, "c"));
+        assert_eq!(step_sizes(&history), [2, 1]);
+    }
+
+    #[test]
+    fn coalesces_consecutive_inserts() {
+        let mut history = History::default();
+        for (idx, text) in ["a", "b", "c"].iter().enumerate() {
+            history.begin_step(Snapshot::default(), true);
+            history.record(insert(idx, text));
+        }
+        history.begin_step(Snapshot::default(), true);
+        history.record(insert(0, "x")); // not where the previous insert ended
+        assert_eq!(step_sizes(&history), [1, 1]);
+    }
+
+    #[test]
+    fn unannounced_change_starts_a_step() {
+        let mut history = History::default();
+        history.record(insert(0, "a"));
+        assert_eq!(step_sizes(&history), [1]);
+        assert!(history.is_modified());
+    }
+
+    #[test]
+    fn change_after_saving_modifies_the_buffer() {
+        let mut history = History::default();
+        history.begin_step(Snapshot::default(), false);
+        history.record(insert(0, "a"));
+        history.mark_saved();
+        assert!(!history.is_modified());
+        history.record(insert(1, "b"));
+        assert!(history.is_modified());
+        assert_eq!(step_sizes(&history), [1, 1]);
+    }
+
+    #[test]
+    fn change_after_undo_does_not_join_an_older_step() {
+        let mut history = History::default();
+        for idx in 0..2 {
+            history.begin_step(Snapshot::default(), false);
+            history.record(insert(idx, "a"));
+        }
+        let step = history.pop_undo().unwrap();
+        history.push_redo(step);
+        history.record(insert(1, "b"));
+        assert_eq!(step_sizes(&history), [1, 1]);
+        assert!(history.pop_redo().is_none());
+    }
+}
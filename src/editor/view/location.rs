@@ -1,5 +1,6 @@
-#[derive(Copy, Clone, Default)]
+// Field order matters: Locations are ordered by line first, then by grapheme.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Debug)]
 pub struct Location {
-    pub grapheme_idx: usize,
     pub line_idx: usize,
-}
\ No newline at end of file
+    pub grapheme_idx: usize,
+}
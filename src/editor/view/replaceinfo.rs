@@ -0,0 +1,14 @@
+use regex::Regex;
+use std::ops::Range;
+
+use super::Location;
+
+pub struct ReplaceInfo {
+    pub query: Regex,
+    pub replacement: String,
+    pub expand: bool, // whether references to capture groups in the replacement are expanded
+    pub origin: Location, // where replacing started, so it can stop once it gets there again
+    pub wrapped: bool, // whether the search has wrapped around the end of the buffer
+    pub current: Option<Range<Location>>, // the match waiting to be replaced or skipped
+    pub count: usize,
+}
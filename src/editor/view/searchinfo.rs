@@ -1,9 +1,205 @@
-use crate::editor::{Line, Position};
+use regex::{Error, Regex, RegexBuilder};
+use std::{fmt, ops::Range};
+
+use crate::editor::Position;
 
 use super::Location;
 
 pub struct SearchInfo {
     pub prev_location: Location,
     pub prev_scroll_offset: Position,
-    pub query: Option<Line>
-}
\ No newline at end of file
+    pub query: Option<Regex>,
+    pub current: Option<Range<Location>>, // the match the caret has been moved to
+    pub wrapped: bool, // whether the last search wrapped around the end or start of the buffer
+    pub match_starts: Option<(usize, Vec<Location>)>, // all match starts, as of a buffer revision
+}
+
+// The position of the current match among all matches of a search
+pub struct MatchCount {
+    pub current: Option<usize>,
+    pub total: usize,
+    pub wrapped: bool,
+}
+
+impl fmt::Display for MatchCount {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.current {
+            _ if self.total == 0 => write!(formatter, "no matches")?,
+            Some(current) => write!(formatter, "{current} of {}", self.total)?,
+            None => write!(formatter, "{} matches", self.total)?,
+        }
+        if self.wrapped {
+            write!(formatter, ", wrapped")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default, Clone, Copy, Eq, PartialEq)]
+pub enum CaseSensitivity {
+    #[default]
+    Sensitive,
+    Insensitive,
+    Smart, // case-sensitive only if the query contains uppercase letters
+}
+
+impl CaseSensitivity {
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Sensitive => Self::Insensitive,
+            Self::Insensitive => Self::Smart,
+            Self::Smart => Self::Sensitive,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct SearchOptions {
+    pub regex: bool,
+    pub case_sensitivity: CaseSensitivity,
+    pub whole_word: bool,
+}
+
+impl SearchOptions {
+    // Turns the query typed into the search prompt into the regex to search for.
+    // Outside of regex mode, the query is searched for literally.
+    // Case folding is left to the regex, which is Unicode-aware and matches on the original text,
+    // so the byte offsets of matches still line up with the graphemes of the line.
+    pub fn compile(self, query: &str) -> Result<Regex, Error> {
+        let mut pattern = if self.regex {
+            query.to_string()
+        } else {
+            regex::escape(query)
+        };
+        if self.whole_word {
+            // A literal query only needs a word boundary where it starts or ends with a word character,
+            // otherwise a query like "foo(" could never match.
+            let is_word_char =
+                |ch: Option<char>| ch.is_some_and(|ch| ch.is_alphanumeric() || ch == '_');
+            let start = if self.regex || is_word_char(query.chars().next()) {
+                r"\b"
+            } else {
+                ""
+            };
+            let end = if self.regex || is_word_char(query.chars().last()) {
+                r"\b"
+            } else {
+                ""
+            };
+            pattern = format!("{start}(?:{pattern}){end}");
+        }
+        let case_insensitive = match self.case_sensitivity {
+            CaseSensitivity::Sensitive => false,
+            CaseSensitivity::Insensitive => true,
+            CaseSensitivity::Smart => !query.chars().any(char::is_uppercase),
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+    }
+    // Describes the options which differ from a plain, case-sensitive search, for showing them in prompts.
+    pub fn labels(self) -> Vec<&'static str> {
+        let mut labels = Vec::new();
+        if self.regex {
+            labels.push("regex");
+        }
+        match self.case_sensitivity {
+            CaseSensitivity::Sensitive => {}
+            CaseSensitivity::Insensitive => labels.push("ignore case"),
+            CaseSensitivity::Smart => labels.push("smart case"),
+        }
+        if self.whole_word {
+            labels.push("whole word");
+        }
+        labels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(options: SearchOptions, query: &str, text: &str) -> Vec<String> {
+        let regex = options.compile(query).unwrap();
+        regex
+            .find_iter(text)
+            .map(|found| found.as_str().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn literal_queries_are_escaped() {
+        let literal = SearchOptions::default();
+        assert_eq!(matches(literal, "a.b", "axb a.b"), ["a.b"]);
+        assert_eq!(matches(literal, "f(x)*", "f(x) f(x)*"), ["f(x)*"]);
+        assert_eq!(matches(literal, r"\d+", r"42 \d+"), [r"\d+"]);
+        let regex = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        assert_eq!(matches(regex, "a.b", "axb a.b"), ["axb", "a.b"]);
+    }
+
+    #[test]
+    fn whole_words() {
+        let whole_word = SearchOptions {
+            whole_word: true,
+            ..SearchOptions::default()
+        };
+        assert_eq!(
+            matches(whole_word, "foo", "foo food _foo foo_bar foo"),
+            ["foo", "foo"]
+        );
+        assert_eq!(matches(whole_word, "für", "für fürs für"), ["für", "für"]);
+        // no boundary next to the non-word characters at either end
+        assert_eq!(matches(whole_word, "foo(", "foo(1) barfoo(2)"), ["foo("]);
+        assert_eq!(matches(whole_word, "(x)", "f(x) (x)y"), ["(x)", "(x)"]);
+        let regex = SearchOptions {
+            regex: true,
+            whole_word: true,
+            ..SearchOptions::default()
+        };
+        // the boundaries wrap the whole alternation, not just its first and last branch
+        assert_eq!(matches(regex, "ab|cd", "abc xcd ab cd"), ["ab", "cd"]);
+    }
+
+    #[test]
+    fn case_sensitivity() {
+        let with_case = |case_sensitivity| SearchOptions {
+            case_sensitivity,
+            ..SearchOptions::default()
+        };
+        let sensitive = with_case(CaseSensitivity::Sensitive);
+        let insensitive = with_case(CaseSensitivity::Insensitive);
+        let smart = with_case(CaseSensitivity::Smart);
+        assert_eq!(matches(sensitive, "ärger", "Ärger ärger ÄRGER"), ["ärger"]);
+        assert_eq!(
+            matches(insensitive, "ärger", "Ärger ärger ÄRGER"),
+            ["Ärger", "ärger", "ÄRGER"]
+        );
+        assert_eq!(
+            matches(smart, "ärger", "Ärger ärger ÄRGER"),
+            ["Ärger", "ärger", "ÄRGER"]
+        );
+        // non-ASCII uppercase letters make a smart search case-sensitive as well
+        assert_eq!(matches(smart, "Ärger", "Ärger ärger ÄRGER"), ["Ärger"]);
+        assert_eq!(matches(smart, "σΣ", "σς σΣ ΣΣ"), ["σΣ"]);
+    }
+
+    #[test]
+    fn invalid_regex_is_reported() {
+        let regex = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        for query in ["(", "a{2", "[z-a]", r"\p{Nope}"] {
+            assert!(regex.compile(query).is_err(), "{query} should not compile");
+        }
+        let error = regex.compile("(").unwrap_err().to_string();
+        assert!(error.contains("unclosed group"), "{error}");
+        // the same queries are fine when searched for literally
+        for query in ["(", "a{2", "[z-a]", r"\p{Nope}"] {
+            assert_eq!(matches(SearchOptions::default(), query, query), [query]);
+        }
+    }
+}
@@ -0,0 +1,7 @@
+// A row on screen in soft wrap mode: the row_idx-th row a line has been wrapped into.
+// Field order matters: VisualRows are ordered by line first, then by row.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub struct VisualRow {
+    pub line_idx: usize,
+    pub row_idx: usize,
+}
@@ -6,9 +6,23 @@
     clippy::as_conversions,
     clippy::integer_division
 )]
+mod args;
 mod editor;
-use editor::Editor;
+use std::{env, process};
 
+use args::{Action, Args, USAGE};
+use editor::{Editor, NAME, VERSION};
+
+// clippy::print_stdout: Help and version are printed before the editor takes over the terminal
+#[allow(clippy::print_stdout)]
 fn main() {
-    Editor::new().unwrap().run();
-}
\ No newline at end of file
+    match Args::parse(env::args().skip(1)) {
+        Ok(Action::Edit(args)) => Editor::new(args).unwrap().run(),
+        Ok(Action::Help) => println!("{USAGE}"),
+        Ok(Action::Version) => println!("{NAME} {VERSION}"),
+        Err(err) => {
+            eprintln!("{NAME}: {err}\n\n{USAGE}");
+            process::exit(2);
+        }
+    }
+}